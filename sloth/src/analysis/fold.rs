@@ -0,0 +1,114 @@
+use super::AnalysisError;
+use crate::parser::ast::{BinaryOp, Expr, ExprKind, Literal, UnaryOp};
+
+/// Replace the expression with its value if it can be evaluated at compile
+/// time. The operands have to be folded first, so this is done while the
+/// types are propagated from the leaves up.
+pub fn fold(node: &mut Expr) -> Result<(), AnalysisError> {
+    // An integer division by a known zero would trap at runtime
+    if let ExprKind::BinaryOp {
+        op: BinaryOp::Div | BinaryOp::Mod,
+        lhs,
+        rhs,
+    } = &node.kind
+    {
+        if let (Some(Literal::Integer(_)), Some(Literal::Integer(0))) = (scalar(lhs), scalar(rhs)) {
+            return Err(AnalysisError::DivisionByZero(node.span));
+        }
+    }
+
+    if let Some(value) = evaluate(node) {
+        node.kind = ExprKind::Literal(value);
+    }
+
+    Ok(())
+}
+
+/// Value of an expression whose operands were already folded, None if it
+/// depends on anything only known at runtime.
+fn evaluate(node: &Expr) -> Option<Literal> {
+    match &node.kind {
+        ExprKind::Grouping(inner) => scalar(inner),
+        ExprKind::Identifier(identifier) => node.symtable.get_value(identifier)?.constant,
+        ExprKind::UnaryOp { op, value } => match (op, scalar(value)?) {
+            (UnaryOp::Neg, Literal::Integer(value)) => Some(Literal::Integer(value.wrapping_neg())),
+            (UnaryOp::Neg, Literal::Float(value)) => Some(Literal::Float(-value)),
+            (UnaryOp::Not, Literal::Boolean(value)) => Some(Literal::Boolean(!value)),
+            _ => None,
+        },
+        ExprKind::BinaryOp { op, lhs, rhs } => binary(*op, scalar(lhs)?, scalar(rhs)?),
+        _ => None,
+    }
+}
+
+/// Literal of an expression, arrays aren't values that can be folded as they
+/// are allocated at runtime.
+fn scalar(node: &Expr) -> Option<Literal> {
+    match &node.kind {
+        ExprKind::Literal(Literal::Array(_) | Literal::Repeat(..)) => None,
+        ExprKind::Literal(literal) => Some(literal.clone()),
+        _ => None,
+    }
+}
+
+/// Apply an operator the same way the generated code does, so folding doesn't
+/// change the result of the program.
+fn binary(op: BinaryOp, lhs: Literal, rhs: Literal) -> Option<Literal> {
+    use BinaryOp::*;
+    use Literal::{Boolean, Float, Integer, String};
+
+    let value = match (lhs, rhs) {
+        (Integer(l), Integer(r)) => match op {
+            Add => Integer(l.wrapping_add(r)),
+            Sub => Integer(l.wrapping_sub(r)),
+            Mul => Integer(l.wrapping_mul(r)),
+            // Division by zero was reported, overflow is left to happen at runtime
+            Div => Integer(l.checked_div(r)?),
+            Mod => Integer(l.checked_rem(r)?),
+            // Negative exponents multiply the base in zero times
+            Pow => Integer(l.wrapping_pow(r.max(0) as u32)),
+            // Only the low five bits of the amount are used for shifting
+            Shl => Integer(l.wrapping_shl(r as u32)),
+            Shr => Integer(l.wrapping_shr(r as u32)),
+            Lt => Boolean(l < r),
+            Gt => Boolean(l > r),
+            LtEq => Boolean(l <= r),
+            GtEq => Boolean(l >= r),
+            EqEq => Boolean(l == r),
+            NotEq => Boolean(l != r),
+            LogicalAnd => Integer(l & r),
+            LogicalOr => Integer(l | r),
+            Con | Range => return None,
+        },
+        (Float(l), Float(r)) => match op {
+            Add => Float(l + r),
+            Sub => Float(l - r),
+            Mul => Float(l * r),
+            Div => Float(l / r),
+            Mod => Float(l % r),
+            Pow => Float(l.powf(r)),
+            Lt => Boolean(l < r),
+            Gt => Boolean(l > r),
+            LtEq => Boolean(l <= r),
+            GtEq => Boolean(l >= r),
+            EqEq => Boolean(l == r),
+            // Comparisons are ordered, so NaN is never unequal either
+            NotEq => Boolean(!l.is_nan() && !r.is_nan() && l != r),
+            _ => return None,
+        },
+        (Boolean(l), Boolean(r)) => match op {
+            EqEq => Boolean(l == r),
+            NotEq => Boolean(l != r),
+            LogicalAnd => Boolean(l && r),
+            LogicalOr => Boolean(l || r),
+            _ => return None,
+        },
+        (String(l), String(r)) => match op {
+            Con => String(format!("{l}{r}")),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(value)
+}
@@ -1,64 +1,952 @@
+mod fold;
 pub mod setup;
 
-use crate::parser::ast::{AstNode, ExprKind, Stmt, StmtKind};
+use itertools::Itertools;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::ast::{
+    AstNode, BinaryOp, Expr, ExprKind, Function, FunctionKind, Literal, Stmt, StmtKind, UnaryOp,
+};
+use crate::symtable::{Type, ValueSymbol};
 
 #[derive(Debug, thiserror::Error)]
 pub enum AnalysisError {
-    #[error("Mismatched types")]
-    TypeMismatch(u32),
+    /// Expression has a different type than the expected type.
+    #[error("Mismatched types, expected {1} but found {2}")]
+    TypeMismatch(Span, Type, Type),
+    /// Both sides of a binary operation have to be of the same type.
+    #[error("Mismatched types for '{op}', {lhs_type} and {rhs_type}")]
+    OperandMismatch {
+        op: BinaryOp,
+        lhs: Span,
+        lhs_type: Type,
+        rhs: Span,
+        rhs_type: Type,
+    },
+    #[error("Expected a function but found {1}")]
+    NotCallable(Span, Type),
     #[error("Unknown identifier '{1}'")]
-    UnknownIdentifier(u32, String),
+    UnknownIdentifier(Span, String),
+    /// Use of a declaration whose value couldn't be typed. The cause was
+    /// already reported, so these are dropped before analysis returns.
+    #[error("Type of '{1}' is unknown")]
+    UnresolvedType(Span, String),
+    /// Functions can only be called, there is no way to store them.
+    #[error("Functions can't be used as values")]
+    FunctionValue(Span, Type),
+    #[error("Expected a struct but found {1}")]
+    NotAStruct(Span, Type),
+    #[error("Expected an array but found {1}")]
+    NotAnArray(Span, Type),
+    #[error("Expected a pointer but found {1}")]
+    NotAPointer(Span, Type),
+    /// References can only be taken to values that are stored somewhere.
+    #[error("Cannot take a reference to a temporary value")]
+    NotAPlace(Span),
+    #[error("No field '{2}' on type {1}")]
+    UnknownField(Span, Type, String),
+    /// There is no character type, the lexer only recognizes the literals.
+    #[error("Character literals are not supported")]
+    CharacterLiteral(Span),
+    /// The element type of an array literal is taken from its elements.
+    #[error("Can't infer the element type of an empty array")]
+    EmptyArray(Span),
+    #[error("Field '{1}' is specified more than once")]
+    DuplicateField(Span, String),
+    /// Fields of a struct which weren't given a value in a struct literal.
+    #[error("Missing fields {} in initializer of {1}", .2.iter().map(|it| format!("'{it}'")).join(", "))]
+    MissingFields(Span, Type, Vec<String>),
+    #[error("Expected an enum but found {1}")]
+    NotAnEnum(Span, Type),
+    #[error("No variant '{2}' on enum {1}")]
+    UnknownVariant(Span, Type, String),
+    #[error("Variant '{1}' is declared more than once")]
+    DuplicateVariant(Span, String),
+    #[error("Expected {1} fields but found {2}")]
+    FieldCount(Span, usize, usize),
+    /// Variants of an enum which aren't covered by any arm of a match.
+    #[error("Match on {1} does not cover {}", .2.iter().map(|it| format!("'{it}'")).join(", "))]
+    NonExhaustiveMatch(Span, Type, Vec<String>),
+    /// Arms after a wildcard or with a variant matched by an earlier arm.
+    #[error("Unreachable match arm")]
+    UnreachableArm(Span),
+    #[error("Expected {1} arguments but found {2}")]
+    ArgumentCount(Span, usize, usize),
+    /// Arguments have to be of the type of the input they are passed to.
+    #[error("Mismatched types for input '{input}', expected {expected} but found {found}")]
+    ArgumentMismatch {
+        span: Span,
+        input: String,
+        expected: Type,
+        found: Type,
+    },
+    #[error("Type parameter '{1}' is declared more than once")]
+    DuplicateTypeParameter(Span, String),
+    /// Type parameters have to be inferable from the inputs of a function or
+    /// the fields of a struct.
+    #[error("Type parameter '{1}' is never used")]
+    UnusedTypeParameter(Span, String),
+    #[error("Values of type parameter {1} can't be used with operators")]
+    GenericOperand(Span, Type),
+    #[error("Operator '{1}' can't be used with {2}")]
+    InvalidOperand(Span, BinaryOp, Type),
+    #[error("Operator '{1}' can't be used with {2}")]
+    InvalidUnaryOperand(Span, UnaryOp, Type),
+    #[error("Expression has no value")]
+    NoValue(Span),
+    /// Functions with an output have to return a value on every path through
+    /// their body.
+    #[error("Function returning {1} can end without returning a value")]
+    MissingReturn(Span, Type),
+    #[error("Functions without an output can't return a value")]
+    UnexpectedReturnValue(Span),
+    #[error("'return' outside of a function")]
+    NotInFunction(Span),
+    #[error("'{1}' outside of a loop")]
+    NotInLoop(Span, &'static str),
+    #[error("Unknown loop label '{1}'")]
+    UnknownLabel(Span, String),
+    /// Constants and the sizes of arrays have to be known at compile time.
+    #[error("Expression can't be evaluated at compile time")]
+    NotConstant(Span),
+    #[error("Division by zero")]
+    DivisionByZero(Span),
+    /// Constants whose values use each other, from the constant the cycle was
+    /// found at back to itself.
+    #[error("Constant '{}' depends on itself", .1[0])]
+    ConstantCycle(Span, Vec<String>),
+    #[error("Array size {1} is negative")]
+    NegativeSize(Span, i32),
+    /// Values declared with `val`, functions and the bindings of patterns
+    /// can't be assigned to.
+    #[error("Cannot assign to immutable value '{identifier}'")]
+    ImmutableAssignment {
+        span: Span,
+        identifier: String,
+        declaration: Option<Span>,
+    },
     #[error("Unknown error '{1}'")]
-    Unknown(u32, &'static str),
+    Unknown(Span, &'static str),
 }
 
 impl AnalysisError {
-    pub fn line(&self) -> u32 {
+    pub fn span(&self) -> Span {
         match self {
-            AnalysisError::TypeMismatch(line) => *line,
-            AnalysisError::UnknownIdentifier(line, ..) => *line,
-            AnalysisError::Unknown(line, ..) => *line,
+            AnalysisError::TypeMismatch(span, ..) => *span,
+            AnalysisError::OperandMismatch { lhs, rhs, .. } => lhs.to(*rhs),
+            AnalysisError::NotCallable(span, ..) => *span,
+            AnalysisError::UnknownIdentifier(span, ..) => *span,
+            AnalysisError::UnresolvedType(span, ..) => *span,
+            AnalysisError::FunctionValue(span, ..) => *span,
+            AnalysisError::NotAStruct(span, ..) => *span,
+            AnalysisError::NotAnArray(span, ..) => *span,
+            AnalysisError::NotAPointer(span, ..) => *span,
+            AnalysisError::NotAPlace(span) => *span,
+            AnalysisError::UnknownField(span, ..) => *span,
+            AnalysisError::CharacterLiteral(span) => *span,
+            AnalysisError::EmptyArray(span) => *span,
+            AnalysisError::DuplicateField(span, ..) => *span,
+            AnalysisError::MissingFields(span, ..) => *span,
+            AnalysisError::NotAnEnum(span, ..) => *span,
+            AnalysisError::UnknownVariant(span, ..) => *span,
+            AnalysisError::DuplicateVariant(span, ..) => *span,
+            AnalysisError::FieldCount(span, ..) => *span,
+            AnalysisError::NonExhaustiveMatch(span, ..) => *span,
+            AnalysisError::UnreachableArm(span) => *span,
+            AnalysisError::ArgumentCount(span, ..) => *span,
+            AnalysisError::ArgumentMismatch { span, .. } => *span,
+            AnalysisError::DuplicateTypeParameter(span, ..) => *span,
+            AnalysisError::UnusedTypeParameter(span, ..) => *span,
+            AnalysisError::GenericOperand(span, ..) => *span,
+            AnalysisError::InvalidOperand(span, ..) => *span,
+            AnalysisError::InvalidUnaryOperand(span, ..) => *span,
+            AnalysisError::NoValue(span) => *span,
+            AnalysisError::MissingReturn(span, ..) => *span,
+            AnalysisError::UnexpectedReturnValue(span) => *span,
+            AnalysisError::NotInFunction(span) => *span,
+            AnalysisError::NotInLoop(span, _) => *span,
+            AnalysisError::UnknownLabel(span, _) => *span,
+            AnalysisError::NotConstant(span) => *span,
+            AnalysisError::DivisionByZero(span) => *span,
+            AnalysisError::ConstantCycle(span, _) => *span,
+            AnalysisError::NegativeSize(span, _) => *span,
+            AnalysisError::ImmutableAssignment { span, .. } => *span,
+            AnalysisError::Unknown(span, ..) => *span,
+        }
+    }
+}
+
+impl From<&AnalysisError> for Diagnostic {
+    fn from(value: &AnalysisError) -> Self {
+        match value {
+            AnalysisError::TypeMismatch(span, expected, found) => {
+                Diagnostic::error("Mismatched types")
+                    .with_primary(*span, format!("expected {expected}, found {found}"))
+            }
+            AnalysisError::OperandMismatch {
+                op,
+                lhs,
+                lhs_type,
+                rhs,
+                rhs_type,
+            } => Diagnostic::error("Mismatched types")
+                .with_primary(*rhs, format!("expected {lhs_type}, found {rhs_type}"))
+                .with_secondary(*lhs, lhs_type.to_string())
+                .with_note(format!("both sides of '{op}' must have the same type")),
+            AnalysisError::NotCallable(span, typ) => Diagnostic::error(value.to_string())
+                .with_primary(*span, format!("this is of type {typ}")),
+            AnalysisError::UnknownIdentifier(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "not found in this scope")
+            }
+            AnalysisError::UnresolvedType(span, _) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "the declaration has an error"),
+            AnalysisError::FunctionValue(span, typ) => Diagnostic::error(value.to_string())
+                .with_primary(*span, format!("this is of type {typ}"))
+                .with_note("functions can only be called"),
+            AnalysisError::NotAStruct(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "not a struct")
+            }
+            AnalysisError::NotAnArray(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "not an array")
+            }
+            AnalysisError::NotAPointer(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "not a pointer")
+            }
+            AnalysisError::NotAPlace(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "this value isn't stored anywhere")
+                .with_note("store it in a variable first"),
+            AnalysisError::UnknownField(span, _, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "unknown field")
+            }
+            AnalysisError::CharacterLiteral(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "this is a character")
+                .with_note("use a string with a single character instead"),
+            AnalysisError::EmptyArray(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "this array has no elements")
+                .with_note("use '[value; 0]' to create an empty array of the type of value"),
+            AnalysisError::DuplicateField(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "used more than once")
+            }
+            AnalysisError::MissingFields(span, ..) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "missing fields")
+            }
+            AnalysisError::NotAnEnum(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "not an enum")
+            }
+            AnalysisError::UnknownVariant(span, ..) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "unknown variant")
+            }
+            AnalysisError::DuplicateVariant(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "declared more than once")
+            }
+            AnalysisError::FieldCount(span, expected, _) => Diagnostic::error(value.to_string())
+                .with_primary(*span, format!("the variant has {expected} fields")),
+            AnalysisError::NonExhaustiveMatch(span, ..) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "patterns not covered")
+                .with_note("add an arm for every missing variant or a '_' arm"),
+            AnalysisError::UnreachableArm(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "this is already matched by an earlier arm"),
+            AnalysisError::ArgumentCount(span, expected, _) => Diagnostic::error(value.to_string())
+                .with_primary(*span, format!("the function takes {expected} arguments")),
+            AnalysisError::ArgumentMismatch {
+                span,
+                input,
+                expected,
+                found,
+            } => Diagnostic::error("Mismatched types")
+                .with_primary(*span, format!("expected {expected}, found {found}"))
+                .with_note(format!("the argument is passed to the input '{input}'")),
+            AnalysisError::DuplicateTypeParameter(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "declared more than once")
+            }
+            AnalysisError::UnusedTypeParameter(span, _) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "the parameter can't be inferred")
+                .with_note(
+                    "type parameters are inferred from the inputs of functions and the fields of \
+                     structs",
+                ),
+            AnalysisError::GenericOperand(span, typ) => Diagnostic::error(value.to_string())
+                .with_primary(*span, format!("this is of type {typ}")),
+            AnalysisError::InvalidOperand(span, _, typ)
+            | AnalysisError::InvalidUnaryOperand(span, _, typ) => {
+                Diagnostic::error(value.to_string())
+                    .with_primary(*span, format!("this is of type {typ}"))
+            }
+            AnalysisError::NoValue(span) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "this is of type Void")
+            }
+            AnalysisError::MissingReturn(span, _) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "the end of the body can be reached"),
+            AnalysisError::UnexpectedReturnValue(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "the function returns Void")
+                .with_note("declare the output type of the function to return a value"),
+            AnalysisError::NotInFunction(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "'return' can only be used inside of a function"),
+            AnalysisError::NotInLoop(span, keyword) => Diagnostic::error(value.to_string())
+                .with_primary(
+                    *span,
+                    format!("'{keyword}' can only be used inside of a loop"),
+                ),
+            AnalysisError::UnknownLabel(span, _) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "no loop around this has the label"),
+            AnalysisError::NotConstant(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "this depends on values only known at runtime")
+                .with_note("only literals, operators and other constants can be used"),
+            AnalysisError::DivisionByZero(span) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "the divisor of this is zero"),
+            AnalysisError::ConstantCycle(span, cycle) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "the value of this constant can't be evaluated")
+                .with_note(format!(
+                    "the constants use each other: {}",
+                    cycle.iter().map(|it| format!("'{it}'")).join(" -> ")
+                )),
+            AnalysisError::NegativeSize(span, _) => Diagnostic::error(value.to_string())
+                .with_primary(*span, "the size can't be negative"),
+            AnalysisError::ImmutableAssignment {
+                span,
+                identifier,
+                declaration,
+            } => {
+                let diagnostic = Diagnostic::error(value.to_string())
+                    .with_primary(*span, "cannot be assigned to");
+                match declaration {
+                    Some(declaration) => diagnostic
+                        .with_secondary(*declaration, format!("'{identifier}' is declared here")),
+                    None => diagnostic,
+                }
+            }
+            AnalysisError::Unknown(span, _) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "")
+            }
         }
     }
 }
 
-pub fn analyze(root: &mut Stmt) -> Result<(), AnalysisError> {
-    let mut populator = setup::Populator::default();
-    populator.populate_symtable(&root.as_node())?;
+/// Analyze a module, every error found is returned instead of stopping at the
+/// first one.
+pub fn analyze(root: &mut Stmt) -> Result<(), Vec<AnalysisError>> {
+    let mut errors = Vec::new();
 
-    setup::propagate_types_stmt(root)?;
+    let mut populator = setup::Populator;
+    populator.populate_types(&root.as_node(), &mut errors);
+    populator.populate_symtable(&root.as_node(), &mut errors);
 
-    check_usage(&root.as_node())?;
+    setup::propagate_types_module(root, &mut errors);
+
+    check_returns(&root.as_node(), None, &mut errors);
+    check_loops(&root.as_node(), &mut Vec::new(), &mut errors);
+
+    errors.retain(|it| !matches!(it, AnalysisError::UnresolvedType(..)));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
 
     Ok(())
 }
 
-fn check_usage(node: &AstNode) -> Result<(), AnalysisError> {
-    if let AstNode::Expr(expr) = node {
-        if let ExprKind::Identifier(identifier) = &expr.kind {
-            if !expr.symtable.clone().contains(identifier) {
-                return Err(AnalysisError::UnknownIdentifier(
-                    expr.line,
-                    identifier.clone(),
-                ));
+/// Check return statements against the output of the function they are in,
+/// and that functions with an output can't reach the end of their body.
+fn check_returns(node: &AstNode, output: Option<&Type>, errors: &mut Vec<AnalysisError>) {
+    if let AstNode::Stmt(stmt) = node {
+        match &stmt.kind {
+            StmtKind::DefineFunction(Function {
+                identifier,
+                kind: FunctionKind::Normal { body },
+                ..
+            }) => {
+                // The function is missing from the symbol table if its
+                // signature was invalid, which was already reported
+                let Some(ValueSymbol { typ: Type::Function { output, .. }, .. }) =
+                    stmt.symtable.get_value(identifier)
+                else {
+                    return;
+                };
+
+                if *output != Type::Void && !always_returns(body) {
+                    errors.push(AnalysisError::MissingReturn(
+                        body.span.shrink_to_end(),
+                        *output.clone(),
+                    ));
+                }
+
+                check_returns(&body.as_node(), Some(&output), errors);
+                return;
             }
+            StmtKind::Return(value) => match (output, value) {
+                (None, _) => errors.push(AnalysisError::NotInFunction(stmt.span)),
+                (Some(Type::Void), Some(value)) => {
+                    errors.push(AnalysisError::UnexpectedReturnValue(value.span));
+                }
+                (Some(output), None) if *output != Type::Void => {
+                    errors.push(AnalysisError::TypeMismatch(
+                        stmt.span,
+                        output.clone(),
+                        Type::Void,
+                    ));
+                }
+                // Values without a type already failed to type check
+                (
+                    Some(output),
+                    Some(Expr {
+                        typ: Some(typ),
+                        span,
+                        ..
+                    }),
+                ) if typ != output => {
+                    errors.push(AnalysisError::TypeMismatch(
+                        *span,
+                        output.clone(),
+                        typ.clone(),
+                    ));
+                }
+                _ => (),
+            },
+            _ => (),
         }
     }
 
+    for child in node.children() {
+        check_returns(&child, output, errors);
+    }
+}
+
+/// Whether running the statement always ends in a return, so the statements
+/// after it are never reached.
+fn always_returns(stmt: &Stmt) -> bool {
+    match &stmt.kind {
+        StmtKind::Return(_) => true,
+        StmtKind::Block(body) => body.iter().any(always_returns),
+        StmtKind::IfStmt {
+            if_then,
+            else_then: Some(else_then),
+            ..
+        } => always_returns(if_then) && always_returns(else_then),
+        // Loops that never end don't reach the statements after them either
+        StmtKind::WhileStmt {
+            label,
+            condition,
+            body,
+        } => {
+            condition.kind == ExprKind::Literal(Literal::Boolean(true))
+                && !breaks_out(body, label.as_deref(), false)
+        }
+        StmtKind::Loop { label, body } => !breaks_out(body, label.as_deref(), false),
+        // Matches are exhaustive, so one of the arms always runs
+        StmtKind::Match { arms, .. } => arms.iter().all(|it| always_returns(&it.body)),
+        _ => false,
+    }
+}
+
+/// Whether a break in the body of a loop exits the loop, breaks without a
+/// label exit the innermost loop they are in.
+fn breaks_out(stmt: &Stmt, label: Option<&str>, nested: bool) -> bool {
+    let nested = match &stmt.kind {
+        StmtKind::Break(None) => return !nested,
+        StmtKind::Break(Some(target)) => return label == Some(target.as_str()),
+        StmtKind::DefineFunction(_) => return false,
+        StmtKind::WhileStmt { label: inner, .. }
+        | StmtKind::ForStmt { label: inner, .. }
+        | StmtKind::Loop { label: inner, .. } => {
+            // The label of an inner loop shadows the same label outside of it
+            if inner.is_some() && inner.as_deref() == label {
+                return false;
+            }
+            true
+        }
+        _ => nested,
+    };
+
+    stmt.children().any(|child| match child {
+        AstNode::Stmt(child) => breaks_out(child, label, nested),
+        AstNode::Expr(_) => false,
+    })
+}
+
+/// Check that break and continue are only used inside of loops, and that
+/// their labels belong to a loop they are in.
+fn check_loops(node: &AstNode, labels: &mut Vec<Option<String>>, errors: &mut Vec<AnalysisError>) {
     if let AstNode::Stmt(stmt) = node {
-        if let StmtKind::AssignVariable { identifier, .. } = &stmt.kind {
-            if !stmt.symtable.clone().contains(identifier) {
-                return Err(AnalysisError::UnknownIdentifier(
-                    stmt.line,
-                    identifier.clone(),
-                ));
+        match &stmt.kind {
+            StmtKind::Break(label) | StmtKind::Continue(label) => {
+                let keyword = match stmt.kind {
+                    StmtKind::Break(_) => "break",
+                    _ => "continue",
+                };
+
+                match label {
+                    _ if labels.is_empty() => {
+                        errors.push(AnalysisError::NotInLoop(stmt.span, keyword));
+                    }
+                    Some(label) if !labels.contains(&Some(label.clone())) => {
+                        errors.push(AnalysisError::UnknownLabel(stmt.span, label.clone()));
+                    }
+                    _ => (),
+                }
+            }
+            StmtKind::WhileStmt { label, .. }
+            | StmtKind::ForStmt { label, .. }
+            | StmtKind::Loop { label, .. } => {
+                labels.push(label.clone());
+                for child in node.children() {
+                    check_loops(&child, labels, errors);
+                }
+                labels.pop();
+                return;
             }
+            // Functions defined inside of a loop can't exit it
+            StmtKind::DefineFunction(_) => {
+                for child in node.children() {
+                    check_loops(&child, &mut Vec::new(), errors);
+                }
+                return;
+            }
+            _ => (),
         }
     }
 
     for child in node.children() {
-        check_usage(&child)?;
+        check_loops(&child, labels, errors);
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::{analyze, AnalysisError};
+    use crate::lexer::Lexer;
+    use crate::parser::ast::{BinaryOp, UnaryOp};
+    use crate::parser::AstParser;
+    use crate::symtable::{Symbol, SymbolTable, Type};
+
+    fn analyze_source(source: &str) -> Vec<AnalysisError> {
+        let mut root = SymbolTable::new();
+        root.insert("Void".into(), Symbol::Type(Type::Void));
+        root.insert("Int".into(), Symbol::Type(Type::Integer));
+        root.insert("Float".into(), Symbol::Type(Type::Float));
+        root.insert("Bool".into(), Symbol::Type(Type::Boolean));
+        root.insert("String".into(), Symbol::Type(Type::String));
+
+        let tokens = Lexer::new(source).collect_vec();
+        let mut ast = AstParser::parse(tokens, root).unwrap();
+        let errors = analyze(&mut ast).err().unwrap_or_default();
+
+        println!("Errors:\n{errors:#?}\n\n");
+
+        errors
+    }
+
+    #[test]
+    fn inferred_declarations() {
+        let errors = analyze_source(
+            "fn main() Int { var x = 5; val y = x + 1; var z: Float = y; return 0; }",
+        );
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::TypeMismatch(
+            _,
+            Type::Float,
+            Type::Integer
+        )]));
+    }
+
+    #[test]
+    fn empty_array_is_reported_once() {
+        let errors = analyze_source("fn main() Int { var x = []; var y = x; y; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::EmptyArray(_)]));
+    }
+
+    #[test]
+    fn argument_count_mismatch() {
+        let errors = analyze_source(
+            "fn add(a: Int, b: Int) Int { return a + b; } fn main() Int { return add(1); }",
+        );
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::ArgumentCount(
+            _,
+            2,
+            1
+        )]));
+    }
+
+    #[test]
+    fn argument_type_mismatch() {
+        let errors = analyze_source(
+            "fn add(a: Int, b: Int) Int { return a + b; } fn main() Int { return add(1, 2.0); }",
+        );
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::ArgumentMismatch { input, expected: Type::Integer, found: Type::Float, .. }]
+                if input == "b"
+        ));
+    }
+
+    #[test]
+    fn missing_return() {
+        let errors = analyze_source("fn f(x: Int) Int { if x > 0 { return 1; } }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::MissingReturn(
+            _,
+            Type::Integer
+        )]));
+    }
+
+    #[test]
+    fn unexpected_return_value() {
+        let errors = analyze_source("fn f() { return 1; }");
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::UnexpectedReturnValue(_)
+        ]));
+    }
+
+    #[test]
+    fn return_outside_of_function() {
+        let errors = analyze_source("return 3;");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::NotInFunction(
+            _
+        )]));
+    }
+
+    #[test]
+    fn assignment_to_val() {
+        let errors = analyze_source("fn main() Int { val x = 1; x = 2; x += 3; return x; }");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                AnalysisError::ImmutableAssignment { identifier: first, .. },
+                AnalysisError::ImmutableAssignment { identifier: second, .. },
+            ] if first == "x" && second == "x"
+        ));
+    }
+
+    #[test]
+    fn constants_out_of_order() {
+        let errors = analyze_source(
+            "const A: Int = B + 1; const B: Int = 2; fn main() Int { var x = [0; A]; return 0; }",
+        );
+
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn constant_cycle() {
+        let errors = analyze_source("const A: Int = B; const B: Int = A + 1; const C: Int = A;");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::ConstantCycle(_, cycle)] if cycle == &["A", "B", "A"]
+        ));
+    }
+
+    #[test]
+    fn constant_division_by_zero() {
+        let errors = analyze_source(
+            "const D: Int = 1 / 0; const M: Int = 1 % (2 - 2); const F: Float = 1.0 / 0.0;",
+        );
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::DivisionByZero(_),
+            AnalysisError::DivisionByZero(_)
+        ]));
+    }
+
+    #[test]
+    fn non_constant_array_size() {
+        let errors = analyze_source("fn main() Int { var n = 3; var x = [0; n]; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::NotConstant(_)]));
+    }
+
+    #[test]
+    fn negative_array_size() {
+        let errors =
+            analyze_source("const N: Int = 2 - 5; fn main() Int { var x = [0; N]; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::NegativeSize(
+            _,
+            -3
+        )]));
+    }
+
+    #[test]
+    fn break_outside_of_loop() {
+        let errors = analyze_source("fn main() Int { break; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::NotInLoop(
+            _,
+            "break"
+        )]));
+    }
+
+    #[test]
+    fn unknown_loop_label() {
+        let errors =
+            analyze_source("fn main() Int { outer: loop { loop { continue inner; } } return 0; }");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::UnknownLabel(_, label)] if label == "inner"
+        ));
+    }
+
+    #[test]
+    fn compound_assignment_on_unsupported_type() {
+        let errors = analyze_source(
+            "fn main() Int { var s: String = \"a\"; s -= \"b\"; s ++= \"c\"; return 0; }",
+        );
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::InvalidOperand(_, BinaryOp::Sub, Type::String)
+        ]));
+    }
+
+    #[test]
+    fn operator_on_unsupported_type() {
+        let errors = analyze_source("fn main() Int { val b = true < false; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::InvalidOperand(_, BinaryOp::Lt, Type::Boolean)
+        ]));
+    }
+
+    #[test]
+    fn unary_operator_on_unsupported_type() {
+        let errors = analyze_source("fn main() Int { val b = !3; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::InvalidUnaryOperand(_, UnaryOp::Not, Type::Integer)
+        ]));
+    }
+
+    #[test]
+    fn reference_to_temporary() {
+        let errors = analyze_source("fn main() Int { val p = *(1 + 2); return 0; }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::NotAPlace(_)]));
+    }
+
+    #[test]
+    fn character_literal() {
+        let errors = analyze_source("fn main() Int { val c = 'a'; val d = c; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::CharacterLiteral(_)
+        ]));
+    }
+
+    const POINT: &str = "struct Point { x: Int, y: Int }";
+
+    #[test]
+    fn struct_literal_missing_fields() {
+        let errors = analyze_source(&format!(
+            "{POINT} fn main() Int {{ val p = Point {{ x: 1 }}; return 0; }}"
+        ));
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::MissingFields(_, _, fields)] if fields == &["y"]
+        ));
+    }
+
+    #[test]
+    fn struct_literal_duplicate_field() {
+        let errors = analyze_source(&format!(
+            "{POINT} fn main() Int {{ val p = Point {{ x: 1, y: 2, x: 3 }}; return 0; }}"
+        ));
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::DuplicateField(_, field)] if field == "x"
+        ));
+    }
+
+    #[test]
+    fn unknown_field() {
+        let errors = analyze_source(&format!(
+            "{POINT} fn main() Int {{ val p = Point {{ x: 1, y: 2 }}; return p.z; }}"
+        ));
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::UnknownField(_, _, field)] if field == "z"
+        ));
+    }
+
+    #[test]
+    fn struct_literal_of_non_struct() {
+        let errors = analyze_source("fn main() Int { val x = Int { y: 1 }; return 0; }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::NotAStruct(
+            _,
+            Type::Integer
+        )]));
+    }
+
+    #[test]
+    fn field_assignment_to_val() {
+        let errors = analyze_source(&format!(
+            "{POINT} fn main() Int {{ val p = Point {{ x: 1, y: 2 }}; p.x = 3; return 0; }}"
+        ));
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::ImmutableAssignment { identifier, .. }] if identifier == "p"
+        ));
+    }
+
+    const SHAPE: &str = "enum Shape { Square(Int), Rect(Int, Int), Empty }";
+
+    #[test]
+    fn match_missing_arm() {
+        let errors = analyze_source(&format!(
+            "{SHAPE} fn area(s: Shape) Int {{ return match s {{ Shape::Square(x) => x, \
+             Shape::Empty => 0 }}; }}"
+        ));
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::NonExhaustiveMatch(_, _, missing)] if missing == &["Rect"]
+        ));
+    }
+
+    #[test]
+    fn match_duplicate_arm() {
+        let errors = analyze_source(&format!(
+            "{SHAPE} fn area(s: Shape) Int {{ return match s {{ Shape::Square(x) => x, \
+             Shape::Square(y) => y, _ => 0 }}; }}"
+        ));
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::UnreachableArm(_)
+        ]));
+    }
+
+    #[test]
+    fn match_arm_after_wildcard() {
+        let errors = analyze_source(&format!(
+            "{SHAPE} fn area(s: Shape) Int {{ return match s {{ _ => 0, Shape::Empty => 1 }}; }}"
+        ));
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::UnreachableArm(_)
+        ]));
+    }
+
+    #[test]
+    fn match_wrong_field_count() {
+        let errors = analyze_source(&format!(
+            "{SHAPE} fn area(s: Shape) Int {{ return match s {{ Shape::Rect(w) => w, _ => 0 }}; }}"
+        ));
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::FieldCount(
+            _,
+            2,
+            1
+        )]));
+    }
+
+    #[test]
+    fn match_unknown_variant() {
+        let errors = analyze_source(&format!(
+            "{SHAPE} fn area(s: Shape) Int {{ return match s {{ Shape::Circle(r) => r, _ => 0 }}; \
+             }}"
+        ));
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::UnknownVariant(_, _, variant)] if variant == "Circle"
+        ));
+    }
+
+    #[test]
+    fn match_on_non_enum() {
+        let errors = analyze_source("fn f(x: Int) Int { return match x { _ => 0 }; }");
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::NotAnEnum(
+            _,
+            Type::Integer
+        )]));
+    }
+
+    #[test]
+    fn generic_call_output_is_inferred() {
+        let errors = analyze_source(
+            "fn first<A, B>(a: A, b: B) A { return a; } fn main() Int { val x = first(1.5, 2); \
+             val y: Int = x; return 0; }",
+        );
+
+        assert!(matches!(errors.as_slice(), [AnalysisError::TypeMismatch(
+            _,
+            Type::Integer,
+            Type::Float
+        )]));
+    }
+
+    #[test]
+    fn unused_type_parameter() {
+        let errors = analyze_source("fn make<T>() Int { return 0; }");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::UnusedTypeParameter(_, param)] if param == "T"
+        ));
+    }
+
+    #[test]
+    fn duplicate_type_parameter() {
+        let errors = analyze_source("fn pick<T, T>(a: T) T { return a; }");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::DuplicateTypeParameter(_, param)] if param == "T"
+        ));
+    }
+
+    #[test]
+    fn operator_on_generic_value() {
+        let errors = analyze_source("fn add<T>(a: T, b: T) T { return a + b; }");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::GenericOperand(_, Type::Param(param))] if param == "T"
+        ));
+    }
+
+    #[test]
+    fn global_declared_after_use() {
+        let errors = analyze_source(
+            "fn bump() Int { count = count + STEP; return count + offset; } var count = 0; val \
+             offset: Int = STEP * 2; const STEP: Int = 3;",
+        );
+
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn global_val_assignment() {
+        let errors = analyze_source("fn reset() { limit = 0; } val limit = 10;");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [AnalysisError::ImmutableAssignment { identifier, .. }] if identifier == "limit"
+        ));
+    }
+
+    #[test]
+    fn function_as_value() {
+        let errors = analyze_source(
+            "fn add(a: Int, b: Int) Int { return a + b; } fn main() Int { val g = add; val h = \
+             [add]; return add(1, 2); }",
+        );
+
+        assert!(matches!(errors.as_slice(), [
+            AnalysisError::FunctionValue(_, Type::Function { .. }),
+            AnalysisError::FunctionValue(_, Type::Function { .. }),
+        ]));
+    }
 }
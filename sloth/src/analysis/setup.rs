@@ -1,106 +1,274 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use super::fold::fold;
 use super::AnalysisError;
+use crate::lexer::Span;
 use crate::parser::ast::{
-    AstNode, BinaryOp, Expr, ExprKind, Function, FunctionInput, FunctionKind, Literal, Stmt,
-    StmtKind, TypeIdentifier,
+    AstNode, BinaryOp, Enum, Expr, ExprKind, Function, FunctionInput, FunctionKind, Literal,
+    Pattern, Stmt, StmtKind, Struct, TypeIdentifier, UnaryOp,
 };
 use crate::symtable::{Symbol, SymbolTable, Type, ValueSymbol};
 
-#[derive(Default)]
-pub struct Populator {
-    next_id: i32,
-}
-
-impl Populator {
-    fn reserve_id(&mut self) -> i32 {
-        let id = self.next_id;
-        self.next_id += 1;
-        id
-    }
-}
+pub struct Populator;
 
 impl Populator {
-    pub(super) fn populate_symtable(&mut self, node: &AstNode) -> Result<(), AnalysisError> {
+    /// Add the types defined by struct statements to the symbol tables. This
+    /// is done before any other symbol is added so functions and variables can
+    /// use structs defined further down.
+    pub(super) fn populate_types(&mut self, node: &AstNode, errors: &mut Vec<AnalysisError>) {
         if let AstNode::Stmt(stmt) = node {
             let mut table = stmt.symtable.clone();
+            let definition = match &stmt.kind {
+                StmtKind::DefineStruct(definition) => Some((
+                    &definition.identifier,
+                    self.build_struct_type(stmt.span, &table, definition),
+                )),
+                StmtKind::DefineEnum(definition) => Some((
+                    &definition.identifier,
+                    self.build_enum_type(stmt.span, &table, definition),
+                )),
+                _ => None,
+            };
 
-            match &stmt.kind {
-                StmtKind::DefineVariable {
-                    identifier, typ, ..
-                } => {
-                    // When a variable is defined add it to the symbol table of the current scope.
-                    let symbol = self.build_value_symbol(
-                        node.line(),
-                        &table,
-                        &typ.clone().unwrap_or(TypeIdentifier {
-                            name: "Float".to_owned(),
-                            is_list: false,
-                        }),
-                        true,
-                    )?;
-                    table.insert(identifier.to_owned(), symbol);
+            match definition {
+                Some((identifier, Ok(typ))) => {
+                    table.insert(identifier.to_owned(), Symbol::Type(typ));
                 }
-                StmtKind::DefineValue {
-                    identifier, typ, ..
-                } => {
-                    // When a variable is defined add it to the symbol table of the current scope.
-                    let symbol = self.build_value_symbol(
-                        node.line(),
-                        &table,
-                        &typ.clone().unwrap_or(TypeIdentifier {
-                            name: "Float".to_owned(),
-                            is_list: false,
-                        }),
-                        false,
-                    )?;
-                    table.insert(identifier.to_owned(), symbol);
-                }
-                StmtKind::DefineFunction(Function {
-                    identifier,
-                    inputs,
-                    output,
-                    kind,
-                }) => {
-                    // When a function is defined add the function to the symbol
-                    // table of the current scope, and add the inputs to the child
-                    // (body) scope.
-                    let function_symbol =
-                        self.build_function_symbol(node.line(), &table, inputs, output.as_ref())?;
-                    table.insert(identifier.to_owned(), function_symbol);
-
-                    if let FunctionKind::Normal { body } = kind {
-                        let mut body_table = body.symtable.clone();
-
-                        for input in inputs {
-                            let symbol = self.build_value_symbol(
-                                node.line(),
-                                &body_table,
-                                &input.typ,
-                                true,
-                            )?;
-                            body_table.insert(input.identifier.to_owned(), symbol);
-                        }
+                Some((_, Err(error))) => errors.push(error),
+                None => (),
+            }
+        }
+
+        for child in node.children() {
+            self.populate_types(&child, errors);
+        }
+    }
+
+    /// Add the symbols defined by every statement to the symbol tables, errors
+    /// are collected so the remaining statements are still populated.
+    pub(super) fn populate_symtable(&mut self, node: &AstNode, errors: &mut Vec<AnalysisError>) {
+        let result = match node {
+            AstNode::Stmt(stmt) => self.populate_stmt(stmt),
+            AstNode::Expr(expr) => self.populate_expr(expr),
+        };
+
+        if let Err(error) = result {
+            errors.push(error);
+        }
+
+        for child in node.children() {
+            self.populate_symtable(&child, errors);
+        }
+    }
+
+    fn populate_stmt(&mut self, stmt: &Stmt) -> Result<(), AnalysisError> {
+        let mut table = stmt.symtable.clone();
+
+        match &stmt.kind {
+            StmtKind::DefineVariable {
+                identifier,
+                typ: Some(typ),
+                ..
+            } => {
+                // When a variable is defined add it to the symbol table of the current scope.
+                // Variables without a type are added once the type of their value is known.
+                let symbol = self.build_value_symbol(stmt.span, &table, typ, true)?;
+                table.insert(identifier.to_owned(), symbol);
+            }
+            StmtKind::DefineValue {
+                identifier,
+                typ: Some(typ),
+                ..
+            } => {
+                let symbol = self.build_value_symbol(stmt.span, &table, typ, false)?;
+                table.insert(identifier.to_owned(), symbol);
+            }
+            StmtKind::DefineConst {
+                identifier, typ, ..
+            } => {
+                // The value of the constant is only known once it's evaluated
+                let symbol = self.build_value_symbol(stmt.span, &table, typ, false)?;
+                table.insert(identifier.to_owned(), symbol);
+            }
+            StmtKind::DefineFunction(Function {
+                identifier,
+                params,
+                inputs,
+                output,
+                kind,
+            }) => {
+                // When a function is defined add the function to the symbol
+                // table of the current scope, and add the type parameters and
+                // inputs to the child (body) scope.
+                let mut scope = match kind {
+                    FunctionKind::Normal { body } => body.symtable.clone(),
+                    FunctionKind::Foreign => table.make_child(),
+                };
+                let params_result = self.populate_params(stmt.span, &mut scope, params);
+
+                let function_symbol =
+                    self.build_function_symbol(stmt.span, &scope, inputs, output.as_ref())?;
+                let Symbol::Value(ValueSymbol {
+                    typ: Type::Function { inputs: input_types, .. },
+                    ..
+                }) = &function_symbol
+                else {
+                    unreachable!("Function symbols have a function type");
+                };
+
+                // Type parameters are inferred from the arguments of calls
+                let unused = params
+                    .iter()
+                    .find(|param| !input_types.iter().any(|it| it.mentions(param)))
+                    .cloned();
+                table.insert(identifier.to_owned(), function_symbol);
+
+                if let FunctionKind::Normal { body } = kind {
+                    let mut body_table = body.symtable.clone();
+
+                    for input in inputs {
+                        let symbol =
+                            self.build_value_symbol(stmt.span, &body_table, &input.typ, true)?;
+                        body_table.insert(input.identifier.to_owned(), symbol);
                     }
                 }
-                StmtKind::ForStmt {
-                    identifier, body, ..
-                } => {
-                    // When a for statement exists we must bind the identifier
-                    // to the value of the iterator.
-                    let mut body_table = body.symtable.clone();
-                    let symbol = Symbol::Value(ValueSymbol {
-                        typ: Type::Integer,
-                        id: self.reserve_id(),
-                        mutable: true,
-                    });
 
-                    body_table.insert(identifier.to_owned(), symbol);
+                // Errors in the type parameters are reported once the symbols
+                // are added so they don't cause errors at every use
+                params_result?;
+                if let Some(param) = unused {
+                    return Err(AnalysisError::UnusedTypeParameter(stmt.span, param));
                 }
-                _ => (),
             }
+            StmtKind::ForStmt {
+                identifier, body, ..
+            } => {
+                // When a for statement exists we must bind the identifier
+                // to the value of the iterator.
+                let mut body_table = body.symtable.clone();
+                let symbol = Symbol::Value(ValueSymbol {
+                    typ: Type::Integer,
+                    id: body_table.reserve_id(),
+                    mutable: true,
+                    span: Some(stmt.span),
+                    constant: None,
+                    inputs: Vec::new(),
+                });
+
+                body_table.insert(identifier.to_owned(), symbol);
+            }
+            StmtKind::Match { arms, .. } => {
+                for arm in arms {
+                    self.populate_pattern(arm.pattern_span, &arm.body.symtable, &arm.pattern)?;
+                }
+            }
+            _ => (),
         }
 
-        for child in node.children() {
-            self.populate_symtable(&child)?;
+        Ok(())
+    }
+
+    fn populate_expr(&mut self, expr: &Expr) -> Result<(), AnalysisError> {
+        if let ExprKind::Match { arms, .. } = &expr.kind {
+            for arm in arms {
+                self.populate_pattern(arm.pattern_span, &arm.body.symtable, &arm.pattern)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the symbols bound by the pattern of a match arm to the table of its
+    /// body. Bindings can't be assigned to.
+    fn populate_pattern(
+        &mut self,
+        span: Span,
+        table: &SymbolTable,
+        pattern: &Pattern,
+    ) -> Result<(), AnalysisError> {
+        let Pattern::Variant {
+            enumeration,
+            variant,
+            bindings,
+        } = pattern
+        else {
+            return Ok(());
+        };
+
+        // Bindings of invalid patterns are still defined so their uses aren't
+        // reported as well
+        let fields = self.pattern_fields(span, table, enumeration, variant, bindings.len());
+        let types = match &fields {
+            Ok(fields) => fields.clone(),
+            Err(_) => vec![Type::Unknown; bindings.len()],
+        };
+
+        let mut table = table.clone();
+        for (binding, typ) in bindings.iter().zip(types) {
+            if binding == "_" {
+                continue;
+            }
+
+            let symbol = Symbol::Value(ValueSymbol {
+                typ,
+                id: table.reserve_id(),
+                mutable: false,
+                span: Some(span),
+                constant: None,
+                inputs: Vec::new(),
+            });
+            table.insert(binding.to_owned(), symbol);
+        }
+
+        fields.map(|_| ())
+    }
+
+    /// Types of the fields of the variant a pattern matches.
+    fn pattern_fields(
+        &self,
+        span: Span,
+        table: &SymbolTable,
+        enumeration: &str,
+        variant: &str,
+        bindings: usize,
+    ) -> Result<Vec<Type>, AnalysisError> {
+        let name = TypeIdentifier {
+            name: enumeration.to_owned(),
+            args: Vec::new(),
+            is_list: false,
+            is_pointer: false,
+        };
+        let typ = table
+            .get_type(&name)
+            .ok_or_else(|| AnalysisError::UnknownIdentifier(span, enumeration.to_owned()))?;
+        if !matches!(typ, Type::Enum { .. }) {
+            return Err(AnalysisError::NotAnEnum(span, typ));
+        }
+
+        let (_, fields) = typ
+            .variant(variant)
+            .ok_or_else(|| AnalysisError::UnknownVariant(span, typ.clone(), variant.to_owned()))?;
+        if fields.len() != bindings {
+            return Err(AnalysisError::FieldCount(span, fields.len(), bindings));
+        }
+
+        Ok(fields.to_vec())
+    }
+
+    /// Add type parameters to the scope of a generic definition.
+    fn populate_params(
+        &mut self,
+        span: Span,
+        scope: &mut SymbolTable,
+        params: &[String],
+    ) -> Result<(), AnalysisError> {
+        for param in params {
+            let typ = Symbol::Type(Type::Param(param.clone()));
+            if !scope.insert(param.clone(), typ) {
+                return Err(AnalysisError::DuplicateTypeParameter(span, param.clone()));
+            }
         }
 
         Ok(())
@@ -108,207 +276,1000 @@ impl Populator {
 
     fn build_value_symbol(
         &mut self,
-        line: u32,
+        span: Span,
         table: &SymbolTable,
         typ: &TypeIdentifier,
         mutab: bool,
     ) -> Result<Symbol, AnalysisError> {
         let typ = table
             .get_type(typ)
-            .ok_or(AnalysisError::UnknownIdentifier(line, typ.to_string()))?;
+            .ok_or(AnalysisError::UnknownIdentifier(span, typ.to_string()))?;
 
         Ok(Symbol::Value(ValueSymbol {
             typ,
-            id: self.reserve_id(),
+            id: table.reserve_id(),
             mutable: mutab,
+            span: Some(span),
+            constant: None,
+            inputs: Vec::new(),
         }))
     }
 
+    fn build_struct_type(
+        &mut self,
+        span: Span,
+        table: &SymbolTable,
+        definition: &Struct,
+    ) -> Result<Type, AnalysisError> {
+        let mut table = table.make_child();
+        self.populate_params(span, &mut table, &definition.params)?;
+
+        let mut fields: Vec<(String, Type)> = Vec::new();
+        for field in &definition.fields {
+            if fields.iter().any(|(it, _)| *it == field.identifier) {
+                return Err(AnalysisError::DuplicateField(
+                    span,
+                    field.identifier.clone(),
+                ));
+            }
+
+            let typ = table
+                .get_type(&field.typ)
+                .ok_or_else(|| AnalysisError::UnknownIdentifier(span, field.typ.to_string()))?;
+            fields.push((field.identifier.clone(), typ));
+        }
+
+        // Type parameters are inferred from the fields of struct literals
+        for param in &definition.params {
+            if !fields.iter().any(|(_, typ)| typ.mentions(param)) {
+                return Err(AnalysisError::UnusedTypeParameter(span, param.clone()));
+            }
+        }
+
+        let args = definition.params.iter().cloned().map(Type::Param).collect();
+        Ok(Type::Struct {
+            name: definition.identifier.clone(),
+            args,
+            fields,
+        })
+    }
+
+    fn build_enum_type(
+        &mut self,
+        span: Span,
+        table: &SymbolTable,
+        definition: &Enum,
+    ) -> Result<Type, AnalysisError> {
+        let mut variants: Vec<(String, Vec<Type>)> = Vec::new();
+        for variant in &definition.variants {
+            if variants.iter().any(|(it, _)| *it == variant.identifier) {
+                return Err(AnalysisError::DuplicateVariant(
+                    span,
+                    variant.identifier.clone(),
+                ));
+            }
+
+            let fields = variant
+                .fields
+                .iter()
+                .map(|typ| {
+                    table
+                        .get_type(typ)
+                        .ok_or_else(|| AnalysisError::UnknownIdentifier(span, typ.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            variants.push((variant.identifier.clone(), fields));
+        }
+
+        Ok(Type::Enum {
+            name: definition.identifier.clone(),
+            variants,
+        })
+    }
+
     fn build_function_symbol(
         &mut self,
-        line: u32,
+        span: Span,
         table: &SymbolTable,
         inputs: &[FunctionInput],
         output: Option<&TypeIdentifier>,
     ) -> Result<Symbol, AnalysisError> {
+        let get_type = |typ: &TypeIdentifier| {
+            table
+                .get_type(typ)
+                .ok_or_else(|| AnalysisError::UnknownIdentifier(span, typ.to_string()))
+        };
+
+        let names = inputs.iter().map(|it| it.identifier.clone()).collect();
         let inputs = inputs
             .iter()
-            .map(|it| table.get_type(&it.typ))
-            .collect::<Option<Vec<_>>>()
-            .ok_or(AnalysisError::UnknownIdentifier(
-                line,
-                "Error creating function inputs".to_owned(),
-            ))?;
-
-        let output = output
-            .map(|it| table.get_type(it))
-            .unwrap_or(Some(Type::Void))
-            .ok_or(AnalysisError::UnknownIdentifier(
-                line,
-                "Error creating function output".to_owned(),
-            ))?;
+            .map(|it| get_type(&it.typ))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let output = match output {
+            Some(output) => get_type(output)?,
+            None => Type::Void,
+        };
 
         Ok(Symbol::Value(ValueSymbol {
             typ: Type::Function {
                 inputs,
                 output: output.into(),
             },
-            id: self.reserve_id(),
-            mutable: true,
+            id: table.reserve_id(),
+            mutable: false,
+            span: Some(span),
+            constant: None,
+            inputs: names,
         }))
     }
 }
 
-pub(super) fn propagate_types_stmt(node: &mut Stmt) -> Result<(), AnalysisError> {
+/// Propagate the types of every expression in a module. Globals are visible in
+/// every function, so their types are known before the functions are checked.
+/// Constants come first as their values are needed wherever they are used.
+pub(super) fn propagate_types_module(root: &mut Stmt, errors: &mut Vec<AnalysisError>) {
+    let StmtKind::Block(children) = &mut root.kind else {
+        return propagate_types_stmt(root, errors);
+    };
+
+    let (functions, globals): (Vec<_>, Vec<_>) = children
+        .iter_mut()
+        .partition(|it| matches!(it.kind, StmtKind::DefineFunction(_)));
+    let (consts, globals): (Vec<_>, Vec<_>) = globals
+        .into_iter()
+        .partition(|it| matches!(it.kind, StmtKind::DefineConst { .. }));
+
+    let consts = order_consts(consts, errors);
+    for stmt in consts.into_iter().chain(globals).chain(functions) {
+        propagate_types_stmt(stmt, errors);
+    }
+}
+
+/// Sort constants so every constant comes after the constants its value uses.
+/// Constants that depend on themselves are reported and left out, as are the
+/// constants using them since their values can't be known either.
+fn order_consts<'a>(
+    consts: Vec<&'a mut Stmt>,
+    errors: &mut Vec<AnalysisError>,
+) -> Vec<&'a mut Stmt> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        Visiting,
+        Done,
+        Broken,
+    }
+
+    struct Graph<'a> {
+        names: Vec<&'a str>,
+        spans: Vec<Span>,
+        dependencies: Vec<Vec<usize>>,
+        states: Vec<State>,
+        stack: Vec<usize>,
+        order: Vec<usize>,
+    }
+
+    impl Graph<'_> {
+        fn visit(&mut self, index: usize, errors: &mut Vec<AnalysisError>) -> State {
+            match self.states[index] {
+                State::Unvisited => (),
+                State::Visiting => {
+                    let start = self.stack.iter().position(|it| *it == index).unwrap();
+                    let cycle = self.stack[start..]
+                        .iter()
+                        .chain([&index])
+                        .map(|it| self.names[*it].to_owned())
+                        .collect();
+                    errors.push(AnalysisError::ConstantCycle(self.spans[index], cycle));
+
+                    for &member in &self.stack[start..] {
+                        self.states[member] = State::Broken;
+                    }
+                    return State::Broken;
+                }
+                state => return state,
+            }
+
+            self.states[index] = State::Visiting;
+            self.stack.push(index);
+
+            let mut state = State::Done;
+            for dependency in self.dependencies[index].clone() {
+                if self.visit(dependency, errors) == State::Broken {
+                    state = State::Broken;
+                }
+            }
+
+            self.stack.pop();
+
+            // Constants in a cycle were already marked while visiting it
+            if self.states[index] == State::Broken {
+                return State::Broken;
+            }
+
+            self.states[index] = state;
+            if state == State::Done {
+                self.order.push(index);
+            }
+            state
+        }
+    }
+
+    fn uses(node: AstNode, names: &[&str], found: &mut Vec<usize>) {
+        if let AstNode::Expr(Expr {
+            kind: ExprKind::Identifier(identifier),
+            ..
+        }) = node
+        {
+            found.extend(names.iter().position(|it| it == identifier));
+        }
+
+        for child in node.children() {
+            uses(child, names, found);
+        }
+    }
+
+    let order = {
+        let names = consts
+            .iter()
+            .map(|it| match &it.kind {
+                StmtKind::DefineConst { identifier, .. } => identifier.as_str(),
+                _ => unreachable!(),
+            })
+            .collect_vec();
+        let dependencies = consts
+            .iter()
+            .map(|it| {
+                let mut found = Vec::new();
+                uses(it.as_node(), &names, &mut found);
+                found
+            })
+            .collect_vec();
+
+        let mut graph = Graph {
+            spans: consts.iter().map(|it| it.span).collect(),
+            states: vec![State::Unvisited; names.len()],
+            names,
+            dependencies,
+            stack: Vec::new(),
+            order: Vec::new(),
+        };
+        for index in 0..consts.len() {
+            graph.visit(index, errors);
+        }
+
+        graph.order
+    };
+
+    let mut consts = consts.into_iter().map(Some).collect_vec();
+    order
+        .into_iter()
+        .map(|it| consts[it].take().unwrap())
+        .collect()
+}
+
+/// Propagate the types of every expression in a statement. An error only stops
+/// the propagation of the expression it occurred in, so every statement gets
+/// checked.
+pub(super) fn propagate_types_stmt(node: &mut Stmt, errors: &mut Vec<AnalysisError>) {
+    fn expr(expr: &mut Expr, errors: &mut Vec<AnalysisError>) {
+        if let Err(error) = propagate_types(expr) {
+            errors.push(error);
+        }
+    }
+
     match &mut node.kind {
         StmtKind::Block(children) => {
             for child in children {
-                propagate_types_stmt(child)?;
+                propagate_types_stmt(child, errors);
             }
         }
-        StmtKind::ExprStmt(expr) => {
-            propagate_types(expr)?;
-        }
+        StmtKind::ExprStmt(value) => expr(value, errors),
         StmtKind::IfStmt {
             condition,
             if_then,
             else_then,
         } => {
-            propagate_types(condition)?;
-            propagate_types_stmt(if_then)?;
+            expr(condition, errors);
+            propagate_types_stmt(if_then, errors);
             if let Some(else_then) = else_then {
-                propagate_types_stmt(else_then)?;
+                propagate_types_stmt(else_then, errors);
             }
         }
-        StmtKind::WhileStmt { condition, body } => {
-            propagate_types(condition)?;
-            propagate_types_stmt(body)?;
+        StmtKind::WhileStmt {
+            condition, body, ..
+        } => {
+            expr(condition, errors);
+            propagate_types_stmt(body, errors);
         }
         StmtKind::ForStmt { iterator, body, .. } => {
-            propagate_types(iterator)?;
-            propagate_types_stmt(body)?;
+            expr(iterator, errors);
+            propagate_types_stmt(body, errors);
         }
-        StmtKind::DefineVariable { value, .. } => {
-            propagate_types(value)?;
+        StmtKind::Loop { body, .. } => propagate_types_stmt(body, errors),
+        StmtKind::DefineVariable {
+            identifier,
+            value,
+            typ,
+        } => {
+            expr(value, errors);
+            if let Err(error) = define(
+                node.span,
+                &node.symtable,
+                identifier,
+                typ.is_some(),
+                value,
+                true,
+            ) {
+                errors.push(error);
+            }
         }
-        StmtKind::DefineValue { value, .. } => {
-            propagate_types(value)?;
+        StmtKind::DefineValue {
+            identifier,
+            value,
+            typ,
+        } => {
+            expr(value, errors);
+            if let Err(error) = define(
+                node.span,
+                &node.symtable,
+                identifier,
+                typ.is_some(),
+                value,
+                false,
+            ) {
+                errors.push(error);
+            }
         }
-        StmtKind::AssignVariable { value, .. } => {
-            propagate_types(value)?;
+        StmtKind::DefineConst {
+            identifier, value, ..
+        } => {
+            expr(value, errors);
+            if let Err(error) = define_const(&node.symtable, identifier, value) {
+                errors.push(error);
+            }
+        }
+        StmtKind::Assign { target, op, value } => {
+            // Fields and elements belong to the variable they are stored in, so
+            // they can only be changed if the variable is mutable. Values behind
+            // pointers don't belong to a variable. This is checked before the
+            // target is folded.
+            let mut root = &*target;
+            while let ExprKind::FieldAccess { value, .. } | ExprKind::Index { value, .. } =
+                &root.kind
+            {
+                root = value;
+            }
+            if let ExprKind::Identifier(identifier) = &root.kind {
+                match root.symtable.get_value(identifier) {
+                    Some(symbol) if !symbol.mutable => {
+                        errors.push(AnalysisError::ImmutableAssignment {
+                            span: node.span,
+                            identifier: identifier.clone(),
+                            declaration: symbol.span,
+                        });
+                    }
+                    _ => (),
+                }
+            }
+
+            expr(target, errors);
+            expr(value, errors);
+
+            // The types are only checked if both sides could be propagated
+            let (Some(target_type), Some(value_type)) = (&target.typ, &value.typ) else {
+                return;
+            };
+
+            // Compound assignments produce a value of the type of the target, so
+            // only the operator has to be checked
+            if let Some(op) = op.filter(|it| !supports(*it, target_type)) {
+                errors.push(AnalysisError::InvalidOperand(
+                    node.span,
+                    op,
+                    target_type.clone(),
+                ));
+            }
+
+            if target_type != value_type {
+                errors.push(AnalysisError::TypeMismatch(
+                    value.span,
+                    target_type.clone(),
+                    value_type.clone(),
+                ));
+            }
         }
         StmtKind::DefineFunction(function) => {
             if let FunctionKind::Normal { body } = &mut function.kind {
-                propagate_types_stmt(body)?;
+                propagate_types_stmt(body, errors);
             }
         }
-        StmtKind::Return(expr) => {
-            propagate_types(expr)?;
+        StmtKind::Match { value, arms } => {
+            expr(value, errors);
+
+            if value.typ.is_some() {
+                let patterns = arms.iter().map(|it| (&it.pattern, it.pattern_span));
+                if let Err(error) = check_match(value, patterns) {
+                    errors.push(error);
+                }
+            }
+
+            for arm in arms {
+                propagate_types_stmt(&mut arm.body, errors);
+            }
         }
+        StmtKind::Return(Some(value)) => expr(value, errors),
+        StmtKind::Return(None) => (),
+        StmtKind::Break(_)
+        | StmtKind::Continue(_)
+        | StmtKind::DefineStruct(_)
+        | StmtKind::DefineEnum(_)
+        | StmtKind::Use(_) => (),
+    }
+}
+
+/// Store the value of a constant in its symbol, uses of the constant are
+/// folded into the value.
+fn define_const(table: &SymbolTable, identifier: &str, value: &Expr) -> Result<(), AnalysisError> {
+    // Unknown types were already reported
+    let (Some(symbol), Some(value_type)) = (table.get_value(identifier), &value.typ) else {
+        return Ok(());
+    };
+
+    if symbol.typ != *value_type {
+        return Err(AnalysisError::TypeMismatch(
+            value.span,
+            symbol.typ,
+            value_type.clone(),
+        ));
+    }
+
+    // Values that could be evaluated were folded into a literal
+    let constant = match &value.kind {
+        ExprKind::Literal(Literal::Array(_) | Literal::Repeat(..)) => None,
+        ExprKind::Literal(literal) => Some(literal.clone()),
+        _ => None,
+    };
+    let Some(constant) = constant else {
+        return Err(AnalysisError::NotConstant(value.span));
+    };
+
+    if let Some(Symbol::Value(symbol)) = table.get_mut(identifier).as_deref_mut() {
+        symbol.constant = Some(constant);
     }
 
     Ok(())
 }
 
+/// Check the value of a variable definition against the declared type of the
+/// variable. Variables without a declared type get the type of their value,
+/// they are only added to the symbol table now so the value can't refer to
+/// the variable itself.
+fn define(
+    span: Span,
+    table: &SymbolTable,
+    identifier: &str,
+    annotated: bool,
+    value: &Expr,
+    mutable: bool,
+) -> Result<(), AnalysisError> {
+    // The value couldn't be typed, which was already reported. Inferred
+    // declarations are still defined so their uses aren't reported again.
+    let Some(value_type) = value.typ.clone() else {
+        if !annotated {
+            insert_value(table, identifier, span, Type::Unknown, mutable);
+        }
+        return Ok(());
+    };
+
+    if value_type == Type::Void {
+        if !annotated {
+            insert_value(table, identifier, span, Type::Unknown, mutable);
+        }
+        return Err(AnalysisError::NoValue(value.span));
+    }
+
+    if annotated {
+        // The declared type was unknown if there is no symbol, which was
+        // already reported as well
+        return match table.get_value(identifier) {
+            Some(symbol) if symbol.typ != value_type => Err(AnalysisError::TypeMismatch(
+                value.span, symbol.typ, value_type,
+            )),
+            _ => Ok(()),
+        };
+    }
+
+    insert_value(table, identifier, span, value_type, mutable);
+
+    Ok(())
+}
+
+fn insert_value(table: &SymbolTable, identifier: &str, span: Span, typ: Type, mutable: bool) {
+    let symbol = Symbol::Value(ValueSymbol {
+        typ,
+        id: table.reserve_id(),
+        mutable,
+        span: Some(span),
+        constant: None,
+        inputs: Vec::new(),
+    });
+    table.clone().insert(identifier.to_owned(), symbol);
+}
+
 pub(super) fn propagate_types(node: &mut Expr) -> Result<(), AnalysisError> {
-    let typ =
-        match &mut node.kind {
-            ExprKind::Grouping(child) => {
-                propagate_types(child)?;
-                child
-                    .typ
-                    .clone()
-                    .ok_or(AnalysisError::Unknown(node.line, "Error at grouping"))?
-            }
-            ExprKind::Literal(lit) => match lit {
-                Literal::Integer(_) => Type::Integer,
-                Literal::Float(_) => Type::Float,
-                Literal::Boolean(_) => Type::Boolean,
-                Literal::Array(members) => {
-                    let mut last = None;
-                    for member in members {
-                        propagate_types(member)?;
-                        if let Some(ref last) = last {
-                            if member.typ.as_ref().unwrap() != last {
-                                return Err(AnalysisError::TypeMismatch(node.line));
-                            }
+    let typ = match &mut node.kind {
+        ExprKind::Grouping(child) => {
+            propagate_types(child)?;
+            child
+                .typ
+                .clone()
+                .ok_or(AnalysisError::Unknown(node.span, "Error at grouping"))?
+        }
+        ExprKind::Literal(lit) => match lit {
+            Literal::Integer(_) => Type::Integer,
+            Literal::Float(_) => Type::Float,
+            Literal::Boolean(_) => Type::Boolean,
+            Literal::Array(members) => {
+                let mut last = None;
+                for member in members {
+                    propagate_types(member)?;
+                    if let Some(ref last) = last {
+                        let typ = member.typ.as_ref().unwrap();
+                        if typ != last {
+                            return Err(AnalysisError::TypeMismatch(
+                                member.span,
+                                last.clone(),
+                                typ.clone(),
+                            ));
                         }
-                        last = Some(member.typ.clone().unwrap());
                     }
-
-                    last.expect("Literal requires 1 element")
+                    last = Some(member.typ.clone().unwrap());
                 }
-                Literal::String(_) => Type::String,
-                _ => todo!(),
-            },
-            ExprKind::Identifier(identifier) => {
-                let table = node.symtable.clone();
-                table.get_value(identifier).map(|it| it.typ).ok_or(
-                    AnalysisError::UnknownIdentifier(node.line, identifier.to_owned()),
-                )?
-            }
-            ExprKind::BinaryOp { lhs, rhs, op } => {
-                // Propagating the types to the children
-                propagate_types(lhs)?;
-                propagate_types(rhs)?;
-
-                if lhs.typ != rhs.typ {
-                    return Err(AnalysisError::TypeMismatch(node.line));
+
+                Type::Array {
+                    typ: Box::new(last.ok_or(AnalysisError::EmptyArray(node.span))?),
                 }
+            }
+            Literal::Repeat(value, count) => {
+                propagate_types(value)?;
+                propagate_types(count)?;
 
-                match op {
-                    BinaryOp::Add
-                    | BinaryOp::Con
-                    | BinaryOp::Sub
-                    | BinaryOp::Mul
-                    | BinaryOp::Div
-                    | BinaryOp::Mod => lhs
-                        .typ
-                        .clone()
-                        .ok_or(AnalysisError::Unknown(node.line, "Error propagating type"))?,
-                    BinaryOp::Lt
-                    | BinaryOp::Gt
-                    | BinaryOp::LtEq
-                    | BinaryOp::GtEq
-                    | BinaryOp::EqEq
-                    | BinaryOp::NotEq => Type::Boolean,
-                    BinaryOp::LogicalAnd | BinaryOp::LogicalOr => lhs
-                        .typ
-                        .clone()
-                        .ok_or(AnalysisError::Unknown(node.line, "Error popagating type"))?,
-                    BinaryOp::Range => {
-                        Type::Iterator {
-                            typ: Box::new(lhs.typ.clone().ok_or(AnalysisError::Unknown(
-                                node.line,
-                                "Error popagating type",
-                            ))?),
-                        }
+                // The count was folded into a literal if it is a constant
+                match (&count.kind, &count.typ) {
+                    (ExprKind::Literal(Literal::Integer(size)), _) if *size >= 0 => (),
+                    (ExprKind::Literal(Literal::Integer(size)), _) => {
+                        return Err(AnalysisError::NegativeSize(count.span, *size));
+                    }
+                    (_, Some(Type::Integer)) => {
+                        return Err(AnalysisError::NotConstant(count.span));
                     }
+                    (_, typ) => {
+                        return Err(AnalysisError::TypeMismatch(
+                            count.span,
+                            Type::Integer,
+                            typ.clone().unwrap(),
+                        ));
+                    }
+                }
+
+                Type::Array {
+                    typ: Box::new(value.typ.clone().unwrap()),
                 }
             }
-            ExprKind::UnaryOp { value, .. } => {
-                propagate_types(value)?;
+            Literal::String(_) => Type::String,
+            Literal::Character(_) => return Err(AnalysisError::CharacterLiteral(node.span)),
+        },
+        ExprKind::Identifier(identifier) => {
+            // Functions can only be called, they have no value that could be
+            // stored. Callees are typed by the call instead.
+            match value_type(&node.symtable, node.span, identifier)? {
+                typ @ Type::Function { .. } => {
+                    return Err(AnalysisError::FunctionValue(node.span, typ));
+                }
+                typ => typ,
+            }
+        }
+        ExprKind::BinaryOp { lhs, rhs, op } => {
+            // Propagating the types to the children
+            propagate_types(lhs)?;
+            propagate_types(rhs)?;
+
+            if lhs.typ != rhs.typ {
+                return Err(AnalysisError::OperandMismatch {
+                    op: *op,
+                    lhs: lhs.span,
+                    lhs_type: lhs.typ.clone().unwrap(),
+                    rhs: rhs.span,
+                    rhs_type: rhs.typ.clone().unwrap(),
+                });
+            }
 
-                value
+            // Nothing is known about the values of type parameters
+            if let Some(typ @ Type::Param(_)) = &lhs.typ {
+                return Err(AnalysisError::GenericOperand(lhs.span, typ.clone()));
+            }
+
+            if let Some(typ) = lhs.typ.as_ref().filter(|it| !supports(*op, it)) {
+                return Err(AnalysisError::InvalidOperand(lhs.span, *op, typ.clone()));
+            }
+
+            match op {
+                BinaryOp::Add
+                | BinaryOp::Con
+                | BinaryOp::Sub
+                | BinaryOp::Mul
+                | BinaryOp::Div
+                | BinaryOp::Mod
+                | BinaryOp::Pow
+                | BinaryOp::Shl
+                | BinaryOp::Shr => lhs
+                    .typ
+                    .clone()
+                    .ok_or(AnalysisError::Unknown(node.span, "Error propagating type"))?,
+                BinaryOp::Lt
+                | BinaryOp::Gt
+                | BinaryOp::LtEq
+                | BinaryOp::GtEq
+                | BinaryOp::EqEq
+                | BinaryOp::NotEq => Type::Boolean,
+                BinaryOp::LogicalAnd | BinaryOp::LogicalOr => lhs
                     .typ
                     .clone()
-                    .ok_or(AnalysisError::Unknown(node.line, "Error propagating type"))?
+                    .ok_or(AnalysisError::Unknown(node.span, "Error popagating type"))?,
+                BinaryOp::Range => Type::Iterator {
+                    typ: Box::new(
+                        lhs.typ
+                            .clone()
+                            .ok_or(AnalysisError::Unknown(node.span, "Error popagating type"))?,
+                    ),
+                },
+            }
+        }
+        ExprKind::UnaryOp { op, value } => {
+            propagate_types(value)?;
+
+            if let Some(typ @ Type::Param(_)) = &value.typ {
+                return Err(AnalysisError::GenericOperand(value.span, typ.clone()));
             }
-            ExprKind::Call { callee, args } => {
-                propagate_types(callee)?;
-                for arg in args {
-                    propagate_types(arg)?;
+
+            let typ = value
+                .typ
+                .clone()
+                .ok_or(AnalysisError::Unknown(node.span, "Error propagating type"))?;
+
+            match op {
+                // Only values stored somewhere have an address
+                UnaryOp::Reference if !value.is_place() => {
+                    return Err(AnalysisError::NotAPlace(value.span));
+                }
+                UnaryOp::Reference => Type::Pointer { typ: Box::new(typ) },
+                UnaryOp::Dereference => match typ {
+                    Type::Pointer { typ } => *typ,
+                    typ => return Err(AnalysisError::NotAPointer(value.span, typ)),
+                },
+                UnaryOp::Not if typ == Type::Boolean => typ,
+                UnaryOp::Neg if matches!(typ, Type::Integer | Type::Float) => typ,
+                UnaryOp::Not | UnaryOp::Neg => {
+                    return Err(AnalysisError::InvalidUnaryOperand(value.span, *op, typ));
                 }
+            }
+        }
+        ExprKind::Call { callee, args } => {
+            match &callee.kind {
+                ExprKind::Identifier(identifier) => {
+                    callee.typ = Some(value_type(&callee.symtable, callee.span, identifier)?);
+                }
+                _ => propagate_types(callee)?,
+            }
+            for arg in args.iter_mut() {
+                propagate_types(arg)?;
+            }
 
-                let Some(Type::Function { ref output, .. }) = callee.typ else {
-                return Err(AnalysisError::TypeMismatch(node.line));
+            let Some(Type::Function { ref inputs, ref output }) = callee.typ else {
+                return Err(AnalysisError::NotCallable(callee.span, callee.typ.clone().unwrap()));
             };
 
-                *output.clone()
+            if inputs.len() != args.len() {
+                return Err(AnalysisError::ArgumentCount(
+                    node.span,
+                    inputs.len(),
+                    args.len(),
+                ));
             }
-        };
 
+            // Named functions know the names of their inputs, other callees
+            // fall back to the position of the argument
+            let names = match &callee.kind {
+                ExprKind::Identifier(name) => callee
+                    .symtable
+                    .get_value(name)
+                    .map(|symbol| symbol.inputs)
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            };
+
+            // The type parameters of generic functions are inferred from the
+            // arguments, which also gives the type of the output
+            let mut params = HashMap::new();
+            for (index, (input, arg)) in inputs.iter().zip(args.iter()).enumerate() {
+                let arg_type = arg.typ.clone().unwrap();
+                if !input.unify(&arg_type, &mut params) {
+                    return Err(AnalysisError::ArgumentMismatch {
+                        span: arg.span,
+                        input: names
+                            .get(index)
+                            .cloned()
+                            .unwrap_or_else(|| format!("#{}", index + 1)),
+                        expected: input.substitute(&params),
+                        found: arg_type,
+                    });
+                }
+            }
+
+            output.substitute(&params)
+        }
+        ExprKind::StructLiteral { identifier, fields } => {
+            // Generic structs are named without their type arguments, they
+            // are inferred from the values of the fields
+            let table = node.symtable.clone();
+            let typ = match table.get(identifier).as_deref() {
+                Some(Symbol::Type(typ)) => typ.clone(),
+                _ => {
+                    return Err(AnalysisError::UnknownIdentifier(
+                        node.span,
+                        identifier.clone(),
+                    ))
+                }
+            };
+            let Type::Struct { fields: expected, .. } = &typ else {
+                    return Err(AnalysisError::NotAStruct(node.span, typ));
+                };
+
+            for (index, (field, value)) in fields.iter().enumerate() {
+                if fields[..index].iter().any(|(it, _)| it == field) {
+                    return Err(AnalysisError::DuplicateField(value.span, field.clone()));
+                }
+            }
+
+            let mut params = HashMap::new();
+            for (field, value) in fields.iter_mut() {
+                propagate_types(value)?;
+
+                let Some((_, field_type)) = typ.field(field) else {
+                        return Err(AnalysisError::UnknownField(value.span, typ.clone(), field.clone()));
+                    };
+
+                let value_type = value.typ.clone().unwrap();
+                if !field_type.unify(&value_type, &mut params) {
+                    return Err(AnalysisError::TypeMismatch(
+                        value.span,
+                        field_type.substitute(&params),
+                        value_type,
+                    ));
+                }
+            }
+
+            let missing = expected
+                .iter()
+                .filter(|(name, _)| !fields.iter().any(|(it, _)| it == name))
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                return Err(AnalysisError::MissingFields(
+                    node.span,
+                    typ.clone(),
+                    missing,
+                ));
+            }
+
+            typ.substitute(&params)
+        }
+        ExprKind::FieldAccess { value, field } => {
+            propagate_types(value)?;
+
+            let typ = value.typ.clone().unwrap();
+            typ.field(field)
+                .map(|(_, it)| it.clone())
+                .ok_or_else(|| AnalysisError::UnknownField(node.span, typ.clone(), field.clone()))?
+        }
+        ExprKind::Index { value, index } => {
+            propagate_types(value)?;
+            propagate_types(index)?;
+
+            let index_type = index.typ.clone().unwrap();
+            if index_type != Type::Integer {
+                return Err(AnalysisError::TypeMismatch(
+                    index.span,
+                    Type::Integer,
+                    index_type,
+                ));
+            }
+
+            match value.typ.clone().unwrap() {
+                Type::Array { typ } => *typ,
+                typ => return Err(AnalysisError::NotAnArray(value.span, typ)),
+            }
+        }
+        ExprKind::Variant {
+            enumeration,
+            variant,
+            args,
+        } => {
+            let name = TypeIdentifier {
+                name: enumeration.clone(),
+                args: Vec::new(),
+                is_list: false,
+                is_pointer: false,
+            };
+            let typ = node
+                .symtable
+                .get_type(&name)
+                .ok_or_else(|| AnalysisError::UnknownIdentifier(node.span, enumeration.clone()))?;
+            if !matches!(typ, Type::Enum { .. }) {
+                return Err(AnalysisError::NotAnEnum(node.span, typ));
+            }
+
+            let (_, fields) = typ.variant(variant).ok_or_else(|| {
+                AnalysisError::UnknownVariant(node.span, typ.clone(), variant.clone())
+            })?;
+            if fields.len() != args.len() {
+                return Err(AnalysisError::FieldCount(
+                    node.span,
+                    fields.len(),
+                    args.len(),
+                ));
+            }
+
+            for (arg, field) in args.iter_mut().zip(fields) {
+                propagate_types(arg)?;
+
+                let arg_type = arg.typ.clone().unwrap();
+                if arg_type != *field {
+                    return Err(AnalysisError::TypeMismatch(
+                        arg.span,
+                        field.clone(),
+                        arg_type,
+                    ));
+                }
+            }
+
+            typ.clone()
+        }
+        ExprKind::Match { value, arms } => {
+            propagate_types(value)?;
+
+            let patterns = arms.iter().map(|it| (&it.pattern, it.pattern_span));
+            check_match(value, patterns)?;
+
+            // Every arm has to evaluate to the same type
+            let mut typ: Option<Type> = None;
+            for arm in arms {
+                propagate_types(&mut arm.body)?;
+
+                let arm_type = arm.body.typ.clone().unwrap();
+                match typ {
+                    Some(ref typ) if *typ != arm_type => {
+                        return Err(AnalysisError::TypeMismatch(
+                            arm.body.span,
+                            typ.clone(),
+                            arm_type,
+                        ));
+                    }
+                    Some(_) => (),
+                    None => typ = Some(arm_type),
+                }
+            }
+
+            typ.unwrap_or(Type::Void)
+        }
+    };
+
+    // Values that failed to fold are left without a type like other errors
+    fold(node)?;
     node.typ = Some(typ);
 
     Ok(())
 }
+
+/// Type of the value an identifier refers to.
+fn value_type(table: &SymbolTable, span: Span, identifier: &str) -> Result<Type, AnalysisError> {
+    match table.get_value(identifier) {
+        Some(symbol) if symbol.typ == Type::Unknown => {
+            Err(AnalysisError::UnresolvedType(span, identifier.to_owned()))
+        }
+        Some(symbol) => Ok(symbol.typ),
+        None => Err(AnalysisError::UnknownIdentifier(
+            span,
+            identifier.to_owned(),
+        )),
+    }
+}
+
+/// Check that the patterns of a match belong to the enum of the matched value,
+/// that every variant of it is covered and that every arm can be reached.
+/// Unknown variants were already reported when the bindings of the patterns
+/// were populated.
+fn check_match<'a>(
+    value: &Expr,
+    patterns: impl Iterator<Item = (&'a Pattern, Span)>,
+) -> Result<(), AnalysisError> {
+    let typ = value.typ.clone().unwrap();
+    let Type::Enum { name, variants } = &typ else {
+        return Err(AnalysisError::NotAnEnum(value.span, typ));
+    };
+
+    let mut covered = Vec::new();
+    let mut wildcard = false;
+    for (pattern, span) in patterns {
+        if wildcard {
+            return Err(AnalysisError::UnreachableArm(span));
+        }
+
+        match pattern {
+            Pattern::Wildcard => wildcard = true,
+            Pattern::Variant {
+                enumeration,
+                variant,
+                ..
+            } => {
+                if enumeration != name {
+                    let name = TypeIdentifier {
+                        name: enumeration.clone(),
+                        args: Vec::new(),
+                        is_list: false,
+                        is_pointer: false,
+                    };
+                    if let Some(found) = value.symtable.get_type(&name) {
+                        return Err(AnalysisError::TypeMismatch(span, typ.clone(), found));
+                    }
+                }
+
+                if covered.contains(&variant) {
+                    return Err(AnalysisError::UnreachableArm(span));
+                }
+                covered.push(variant);
+            }
+        }
+    }
+
+    let missing = variants
+        .iter()
+        .map(|(variant, _)| variant)
+        .filter(|it| !covered.contains(it))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !wildcard && !missing.is_empty() {
+        return Err(AnalysisError::NonExhaustiveMatch(
+            value.span,
+            typ.clone(),
+            missing,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Whether the operator can be applied to operands of the type, which are the
+/// combinations code can be generated for.
+fn supports(op: BinaryOp, typ: &Type) -> bool {
+    match op {
+        BinaryOp::Add
+        | BinaryOp::Sub
+        | BinaryOp::Mul
+        | BinaryOp::Div
+        | BinaryOp::Mod
+        | BinaryOp::Pow
+        | BinaryOp::Lt
+        | BinaryOp::Gt
+        | BinaryOp::LtEq
+        | BinaryOp::GtEq => matches!(typ, Type::Integer | Type::Float),
+        BinaryOp::EqEq | BinaryOp::NotEq => {
+            matches!(typ, Type::Integer | Type::Float | Type::Boolean)
+        }
+        BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Range => *typ == Type::Integer,
+        BinaryOp::LogicalAnd | BinaryOp::LogicalOr => *typ == Type::Boolean,
+        BinaryOp::Con => *typ == Type::String,
+    }
+}
@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::diagnostics::MessageFormat;
+
+#[derive(Parser, Debug)]
+#[command(name = "sloth", version, about = "Sloth programming language compiler")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compile source files into an executable or another output
+    Build(BuildArgs),
+    /// Check source files for errors without generating any code
+    Check(InputArgs),
+    /// Compile source files and run the resulting program
+    Run(RunArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct InputArgs {
+    /// Source files to compile. C sources and object files are passed on to
+    /// the linker.
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Don't load the standard library or link its runtime
+    #[arg(long)]
+    pub no_std: bool,
+
+    /// Format of error messages
+    #[arg(long, value_enum, default_value_t)]
+    pub message_format: MessageFormat,
+}
+
+impl InputArgs {
+    /// Files containing sloth source code.
+    pub fn sources(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter().filter(|it| !is_linker_input(it))
+    }
+
+    /// C sources and object files which are linked with the program.
+    pub fn linker_inputs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter().filter(|it| is_linker_input(it))
+    }
+}
+
+fn is_linker_input(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|it| it.to_str()),
+        Some("c" | "o" | "a" | "so" | "obj" | "lib")
+    )
+}
+
+#[derive(Args, Debug)]
+pub struct CodegenArgs {
+    /// Optimization level, 0 disables every optimization so the program is
+    /// easy to debug
+    #[arg(short = 'O', long, value_enum, default_value = "2")]
+    pub opt_level: OptLevel,
+
+    /// Target triple to generate code for, defaults to the host
+    #[arg(long)]
+    pub target: Option<String>,
+
+    /// CPU to generate code for, "native" for the CPU of the host. Defaults to
+    /// the host CPU when targeting the host and to "generic" otherwise
+    #[arg(long)]
+    pub cpu: Option<String>,
+
+    /// Target features to enable or disable, such as "+avx2,-sse4.1"
+    #[arg(long)]
+    pub features: Option<String>,
+
+    /// Relocation model, defaults to position independent code
+    #[arg(long, value_enum)]
+    pub reloc: Option<Reloc>,
+
+    /// Generate debug information so the program can be debugged with gdb or
+    /// lldb
+    #[arg(short = 'g', long)]
+    pub debug_info: bool,
+
+    /// Skip checking that indices are within the size of arrays, indexing out
+    /// of range then reads and writes memory outside of the array
+    #[arg(long)]
+    pub unchecked: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct BuildArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub codegen: CodegenArgs,
+
+    /// Kind of output to generate
+    #[arg(long, value_enum, default_value_t = Emit::Exe)]
+    pub emit: Emit,
+
+    /// Path of the output. Defaults to the name of the last source file with
+    /// the extension of the output kind, text outputs are written to stdout.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub codegen: CodegenArgs,
+
+    /// Compile the program in memory and run it inside of the compiler instead
+    /// of linking an executable
+    #[arg(long, conflicts_with_all = ["target", "cpu", "features", "reloc"])]
+    pub jit: bool,
+
+    /// Arguments passed to the program
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    /// Tokens of every source file
+    Tokens,
+    /// Abstract syntax tree of every module
+    Ast,
+    /// Graphviz graph of the abstract syntax tree of every module
+    Dot,
+    /// Textual LLVM IR, written even if it is invalid
+    LlvmIr,
+    /// LLVM bitcode
+    LlvmBc,
+    /// Assembly for the target
+    Asm,
+    /// Object file
+    Obj,
+    /// Linked executable
+    Exe,
+}
+
+impl Emit {
+    /// Extension of the default output path, None if the output is written to
+    /// stdout by default.
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Emit::Tokens | Emit::Ast | Emit::Dot => None,
+            Emit::LlvmIr => Some("ll"),
+            Emit::LlvmBc => Some("bc"),
+            Emit::Asm => Some("s"),
+            Emit::Obj => Some("o"),
+            Emit::Exe => Some(std::env::consts::EXE_EXTENSION),
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum OptLevel {
+    #[value(name = "0")]
+    None,
+    #[value(name = "1")]
+    Less,
+    #[value(name = "2")]
+    Default,
+    #[value(name = "3")]
+    Aggressive,
+    /// Optimize for size
+    #[value(name = "s")]
+    Size,
+}
+
+#[cfg(feature = "llvm")]
+impl From<OptLevel> for inkwell::OptimizationLevel {
+    fn from(value: OptLevel) -> Self {
+        match value {
+            OptLevel::None => Self::None,
+            OptLevel::Less => Self::Less,
+            OptLevel::Default | OptLevel::Size => Self::Default,
+            OptLevel::Aggressive => Self::Aggressive,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Reloc {
+    /// Position independent code
+    Pic,
+    /// Code which can only be loaded at a fixed address
+    Static,
+}
+
+#[cfg(feature = "llvm")]
+impl From<Reloc> for inkwell::targets::RelocMode {
+    fn from(value: Reloc) -> Self {
+        match value {
+            Reloc::Pic => Self::PIC,
+            Reloc::Static => Self::Static,
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::path::Path;
+
+use inkwell::basic_block::BasicBlock;
+use inkwell::context::Context;
+use inkwell::debug_info::{
+    debug_metadata_version, AsDIScope, DICompileUnit, DIFile, DIFlags, DIFlagsConstants,
+    DILocation, DISubprogram, DIType, DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+};
+use inkwell::module::{FlagBehavior, Module};
+use inkwell::targets::TargetData;
+use inkwell::values::{FunctionValue, PointerValue};
+use inkwell::AddressSpace;
+
+use crate::lexer::Span;
+use crate::modules::SourceFile;
+use crate::symtable::Type;
+
+// DWARF type encodings, see section 7.8 of the DWARF 4 standard
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_FLOAT: u32 = 0x04;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_SIGNED_CHAR: u32 = 0x06;
+
+/// DWARF debug information of a module. Source locations are attached to the
+/// instructions of functions and the variables they declare are described so
+/// debuggers can show them.
+pub struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    unit: DICompileUnit<'ctx>,
+    files: Vec<DIFile<'ctx>>,
+    pointer_bits: u64,
+    optimized: bool,
+    /// Subprogram of the function code is currently generated for.
+    scope: Option<DISubprogram<'ctx>>,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+    /// Create the debug information for a program made out of the files, the
+    /// last file is the main source file of the compile unit. Pointer sizes
+    /// are taken from the data layout of the module.
+    pub fn new(module: &Module<'ctx>, files: &[SourceFile], optimized: bool) -> Self {
+        let context = module.get_context();
+        module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            context
+                .i32_type()
+                .const_int(debug_metadata_version() as u64, false),
+        );
+        module.add_basic_value_flag(
+            "Dwarf Version",
+            FlagBehavior::Warning,
+            context.i32_type().const_int(4, false),
+        );
+
+        let main = files.last().map_or(Path::new(""), |it| it.path.as_path());
+        let (name, directory) = file_location(main);
+        let (builder, unit) = module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &name,
+            &directory,
+            "sloth",
+            optimized,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+
+        let files = files
+            .iter()
+            .map(|it| {
+                let (name, directory) = file_location(&it.path);
+                builder.create_file(&name, &directory)
+            })
+            .collect();
+
+        let layout = module.get_data_layout();
+        let pointer_bits =
+            TargetData::create(&layout.as_str().to_string_lossy()).get_pointer_byte_size(None);
+
+        Self {
+            builder,
+            unit,
+            files,
+            pointer_bits: pointer_bits as u64 * 8,
+            optimized,
+            scope: None,
+        }
+    }
+
+    /// Describe a function defined at the span, locations and variables are
+    /// attached to it until the function is finished.
+    pub fn start_function(
+        &mut self,
+        function: FunctionValue<'ctx>,
+        name: &str,
+        span: Span,
+        inputs: &[Type],
+        output: &Type,
+    ) {
+        let file = self.files[span.file];
+        let line = span.start.row + 1;
+
+        // Types without a description are left out, debuggers show them as void
+        let inputs = inputs
+            .iter()
+            .map(|it| self.type_of(it).unwrap_or_else(|| self.unknown_type()))
+            .collect::<Vec<_>>();
+        let signature =
+            self.builder
+                .create_subroutine_type(file, self.type_of(output), &inputs, DIFlags::ZERO);
+
+        let subprogram = self.builder.create_function(
+            self.unit.as_debug_info_scope(),
+            name,
+            None,
+            file,
+            line,
+            signature,
+            false,
+            true,
+            line,
+            DIFlags::ZERO,
+            self.optimized,
+        );
+
+        function.set_subprogram(subprogram);
+        self.scope = Some(subprogram);
+    }
+
+    pub fn finish_function(&mut self) {
+        self.scope = None;
+    }
+
+    /// Location of the span inside of the current function, None outside of
+    /// functions.
+    pub fn location(&self, context: &'ctx Context, span: Span) -> Option<DILocation<'ctx>> {
+        let scope = self.scope?.as_debug_info_scope();
+        Some(self.builder.create_debug_location(
+            context,
+            span.start.row + 1,
+            span.start.col + 1,
+            scope,
+            None,
+        ))
+    }
+
+    /// Describe a variable of the current function stored at the pointer, the
+    /// declaration is added to the end of the block. Parameters are numbered
+    /// starting at 1.
+    #[allow(clippy::too_many_arguments)]
+    pub fn declare_variable(
+        &self,
+        context: &'ctx Context,
+        storage: PointerValue<'ctx>,
+        name: &str,
+        typ: &Type,
+        span: Span,
+        parameter: Option<u32>,
+        block: BasicBlock<'ctx>,
+    ) {
+        let (Some(location), Some(typ)) = (self.location(context, span), self.type_of(typ)) else {
+            return;
+        };
+
+        let scope = location.get_scope();
+        let file = self.files[span.file];
+        let line = span.start.row + 1;
+        let variable = match parameter {
+            Some(number) => self.builder.create_parameter_variable(
+                scope,
+                name,
+                number,
+                file,
+                line,
+                typ,
+                true,
+                DIFlags::ZERO,
+            ),
+            None => self.builder.create_auto_variable(
+                scope,
+                name,
+                file,
+                line,
+                typ,
+                true,
+                DIFlags::ZERO,
+                0,
+            ),
+        };
+
+        self.builder
+            .insert_declare_at_end(storage, Some(variable), None, location, block);
+    }
+
+    /// Resolve the references between the descriptions, has to be done before
+    /// the module is verified or written.
+    pub fn finalize(&self) {
+        self.builder.finalize();
+    }
+
+    /// Description of a type, None for types which can't be described such as
+    /// Void.
+    fn type_of(&self, typ: &Type) -> Option<DIType<'ctx>> {
+        let basic = |name: &str, bits: u64, encoding: u32| {
+            self.builder
+                .create_basic_type(name, bits, encoding, DIFlags::ZERO)
+                .unwrap()
+                .as_type()
+        };
+
+        Some(match typ {
+            Type::Integer => basic("Int", 32, DW_ATE_SIGNED),
+            Type::Float => basic("Float", 32, DW_ATE_FLOAT),
+            Type::Boolean => basic("Bool", 8, DW_ATE_BOOLEAN),
+            Type::String => {
+                let char = basic("char", 8, DW_ATE_SIGNED_CHAR);
+                self.pointer_to("String", char)
+            }
+            Type::Pointer { typ: pointee } => {
+                let pointee = self.type_of(pointee).unwrap_or_else(|| self.unknown_type());
+                self.pointer_to(&typ.to_string(), pointee)
+            }
+            Type::Array { typ } => {
+                // Arrays are pointers to a vector holding their size, capacity
+                // and elements
+                let element = self.type_of(typ).unwrap_or_else(|| self.unknown_type());
+                let int = basic("Int", 32, DW_ATE_SIGNED);
+                let inner = self.pointer_to("", element);
+
+                let members = [("size", int, 0), ("cap", int, 32), ("inner", inner, 64)];
+                let vector =
+                    self.struct_type(&format!("[{typ}]"), &members, 64 + self.pointer_bits, 0);
+                self.pointer_to("", vector)
+            }
+            Type::Struct { fields, .. } => {
+                let (bits, align) = self.layout(typ);
+
+                let mut offset = 0;
+                let mut members = Vec::new();
+                for (field, field_type) in fields {
+                    let (field_bits, field_align) = self.layout(field_type);
+                    offset = align_to(offset, field_align);
+
+                    let member = self
+                        .type_of(field_type)
+                        .unwrap_or_else(|| self.unknown_type());
+                    members.push((field.as_str(), member, offset));
+
+                    offset += field_bits;
+                }
+
+                self.struct_type(&typ.to_string(), &members, bits, align as u32)
+            }
+            Type::Enum { name, .. } => {
+                // Only the tag is described, the payload depends on it
+                let (bits, align) = self.layout(typ);
+                let members = [("tag", basic("Int", 32, DW_ATE_SIGNED), 0)];
+                self.struct_type(name, &members, bits, align as u32)
+            }
+            Type::Void
+            | Type::Iterator { .. }
+            | Type::Function { .. }
+            | Type::Param(_)
+            | Type::Unknown => return None,
+        })
+    }
+
+    fn struct_layout<'a>(&self, fields: impl Iterator<Item = &'a Type>) -> (u64, u64) {
+        let mut bits = 0;
+        let mut align = 8;
+        for field in fields {
+            let (field_bits, field_align) = self.layout(field);
+            bits = align_to(bits, field_align) + field_bits;
+            align = align.max(field_align);
+        }
+
+        (align_to(bits, align), align)
+    }
+
+    /// Description of a struct with members at the given offsets in bits.
+    fn struct_type(
+        &self,
+        name: &str,
+        members: &[(&str, DIType<'ctx>, u64)],
+        bits: u64,
+        align: u32,
+    ) -> DIType<'ctx> {
+        let members = members
+            .iter()
+            .map(|&(name, member, offset)| {
+                self.builder
+                    .create_member_type(
+                        self.unit.as_debug_info_scope(),
+                        name,
+                        self.unit.get_file(),
+                        0,
+                        member.get_size_in_bits(),
+                        0,
+                        offset,
+                        DIFlags::ZERO,
+                        member,
+                    )
+                    .as_type()
+            })
+            .collect::<Vec<_>>();
+
+        self.builder
+            .create_struct_type(
+                self.unit.as_debug_info_scope(),
+                name,
+                self.unit.get_file(),
+                0,
+                bits,
+                align,
+                DIFlags::ZERO,
+                None,
+                &members,
+                0,
+                None,
+                "",
+            )
+            .as_type()
+    }
+
+    /// Size and alignment in bits of a type, following the layout LLVM uses
+    /// for structs where every member is aligned to its own size.
+    fn layout(&self, typ: &Type) -> (u64, u64) {
+        match typ {
+            Type::Integer | Type::Float => (32, 32),
+            Type::Boolean => (8, 8),
+            Type::String | Type::Array { .. } | Type::Pointer { .. } => {
+                (self.pointer_bits, self.pointer_bits)
+            }
+            Type::Struct { fields, .. } => self.struct_layout(fields.iter().map(|(_, it)| it)),
+            Type::Enum { variants, .. } => {
+                // The tag is followed by a payload fitting the largest variant
+                let (payload_bits, payload_align) = variants
+                    .iter()
+                    .map(|(_, fields)| self.struct_layout(fields.iter()))
+                    .fold((0, 8), |(bits, align), (variant_bits, variant_align)| {
+                        (bits.max(variant_bits), align.max(variant_align))
+                    });
+
+                let align = payload_align.max(32);
+                let bits = align_to(32, payload_align) + payload_bits;
+                (align_to(bits, align), align)
+            }
+            Type::Void
+            | Type::Iterator { .. }
+            | Type::Function { .. }
+            | Type::Param(_)
+            | Type::Unknown => (0, 8),
+        }
+    }
+
+    fn pointer_to(&self, name: &str, pointee: DIType<'ctx>) -> DIType<'ctx> {
+        self.builder
+            .create_pointer_type(name, pointee, self.pointer_bits, 0, AddressSpace::default())
+            .as_type()
+    }
+
+    /// Placeholder for types without a description.
+    fn unknown_type(&self) -> DIType<'ctx> {
+        self.builder
+            .create_basic_type("void", 0, 0, DIFlags::ZERO)
+            .unwrap()
+            .as_type()
+    }
+}
+
+fn align_to(bits: u64, align: u64) -> u64 {
+    (bits + align - 1) / align * align
+}
+
+/// Name and directory of a file as they are written to the debug info.
+fn file_location(path: &Path) -> (String, String) {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let name = path
+        .file_name()
+        .map(|it| it.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let directory = path
+        .parent()
+        .map(|it| it.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    (name, directory)
+}
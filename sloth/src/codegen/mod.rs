@@ -1,24 +1,113 @@
 use std::collections::HashMap;
-use std::io::Write;
+use std::io::{self, Write};
 
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::basic_block::BasicBlock;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::module::Module;
+use inkwell::intrinsics::Intrinsic;
+use inkwell::module::{Linkage, Module};
+use inkwell::passes::PassBuilderOptions;
 use inkwell::targets::{
-    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine,
+    TargetTriple,
 };
-use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, StructType};
 use inkwell::values::{
-    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, IntValue, PointerValue,
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, InstructionValue, IntValue,
+    PointerValue,
 };
 use inkwell::{AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel};
 use itertools::{Either, Itertools};
 
+use self::debug::DebugInfo;
+use crate::lexer::Span;
+use crate::modules::SourceFile;
 use crate::parser::ast::{
-    BinaryOp, Expr, ExprKind, Function, FunctionKind, Literal, Stmt, StmtKind, UnaryOp,
+    BinaryOp, Expr, ExprKind, Function, FunctionKind, Literal, Pattern, Stmt, StmtKind, UnaryOp,
 };
 use crate::symtable::{SymbolTable, Type};
 
+mod debug;
+
+#[derive(thiserror::Error, Debug)]
+pub enum CodegenError {
+    #[error("Unknown target '{0}': {1}")]
+    UnknownTarget(String, String),
+    #[error("Could not create a target machine for '{0}'")]
+    TargetMachine(String),
+    #[error("{0}")]
+    Llvm(String),
+    #[error("Generated invalid LLVM IR, this is a bug in the compiler\n{0}")]
+    Verify(String),
+    #[error("Program has no 'main' function")]
+    NoMain,
+    #[error(
+        "Signature of 'main' is not supported, the JIT can only call a main returning Int or Void"
+    )]
+    MainSignature,
+    #[error("Error while writing output: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Options describing the machine code is generated for.
+#[derive(Debug, Clone)]
+pub struct TargetOptions {
+    /// Target triple, the host is targeted if there is none.
+    pub triple: Option<String>,
+    /// CPU to generate code for, "native" is the CPU of the host. Defaults to
+    /// the host CPU if the host is targeted and to "generic" otherwise.
+    pub cpu: Option<String>,
+    /// Features to enable or disable, such as "+avx2,-sse4.1". Default to the
+    /// features of the CPU.
+    pub features: Option<String>,
+    pub reloc: RelocMode,
+    pub opt_level: OptimizationLevel,
+    /// Prefer small code over fast code, the optimization level still applies
+    /// to instruction selection.
+    pub optimize_size: bool,
+}
+
+impl Default for TargetOptions {
+    fn default() -> Self {
+        Self {
+            triple: None,
+            cpu: None,
+            features: None,
+            reloc: RelocMode::Default,
+            opt_level: OptimizationLevel::default(),
+            optimize_size: false,
+        }
+    }
+}
+
+fn triple_name(triple: &TargetTriple) -> String {
+    triple.as_str().to_string_lossy().into_owned()
+}
+
+/// Generic function or intrinsic generated for one combination of types.
+struct Instance<'ctx> {
+    function: FunctionValue<'ctx>,
+    generic: Generic,
+    type_args: HashMap<String, Type>,
+}
+
+/// Loop the builder is inside of, break and continue branch to its blocks.
+struct Loop<'ctx> {
+    label: Option<String>,
+    /// Block starting the next iteration
+    next: BasicBlock<'ctx>,
+    /// Block after the loop
+    exit: BasicBlock<'ctx>,
+}
+
+enum Generic {
+    /// Intrinsic generated by the compiler, by its name
+    Intrinsic(String),
+    /// Generic function written in sloth, by the id of its symbol
+    Function(i32),
+}
+
 pub struct Codegen<'ctx> {
     context: &'ctx Context,
     builder: Builder<'ctx>,
@@ -28,6 +117,27 @@ pub struct Codegen<'ctx> {
     current_func_void: bool,
 
     references: HashMap<i32, PointerValue<'ctx>>,
+    /// Globals by the id of their symbol, they are visible in every function.
+    globals: HashMap<i32, PointerValue<'ctx>>,
+    /// Return at the end of the function initializing globals, initializers of
+    /// later modules are inserted in its place.
+    initializer: Option<InstructionValue<'ctx>>,
+
+    /// Loops around the statement being generated, the innermost one last.
+    loops: Vec<Loop<'ctx>>,
+
+    /// Definitions of generic functions by the id of their symbol, they are
+    /// generated once for every combination of types they are called with.
+    generics: HashMap<i32, Stmt>,
+    /// Instances which were called but haven't been generated yet.
+    instances: Vec<Instance<'ctx>>,
+    /// Types the type parameters stand for in the instance being generated.
+    type_args: HashMap<String, Type>,
+
+    /// Whether indexing checks that the index is within the size of the array.
+    bounds_checks: bool,
+
+    debug: Option<DebugInfo<'ctx>>,
 }
 
 impl<'ctx> Codegen<'ctx> {
@@ -35,7 +145,7 @@ impl<'ctx> Codegen<'ctx> {
         let builder = context.create_builder();
         let module = context.create_module(module);
 
-        let mut this = Codegen {
+        Codegen {
             context,
             builder,
             module,
@@ -44,24 +154,32 @@ impl<'ctx> Codegen<'ctx> {
             current_func_void: false,
 
             references: Default::default(),
-        };
+            globals: Default::default(),
+            initializer: None,
+
+            loops: Default::default(),
+
+            generics: Default::default(),
+            instances: Default::default(),
+            type_args: Default::default(),
+
+            bounds_checks: true,
 
-        // Compiler intrinsic functions
-        this.INTRINSIC_vlen();
-
-        for (c, t) in [
-            ("i", Type::Integer),
-            ("f", Type::Float),
-            ("b", Type::Boolean),
-            ("s", Type::String),
-        ] {
-            this.INTRINSIC_vpush(c, t.clone());
-            this.INTRINSIC_vpop(c, t.clone());
-            this.INTRINSIC_vget(c, t.clone());
-            this.INTRINSIC_vset(c, t);
+            debug: None,
         }
+    }
+
+    /// Index arrays without checking the index first, out of range indices
+    /// read and write past the end of the array.
+    pub fn disable_bounds_checks(&mut self) {
+        self.bounds_checks = false;
+    }
 
-        this
+    /// Generate DWARF debug information for the code generated from now on.
+    /// The target machine has to be created beforehand so the debug info
+    /// matches the data layout of the target.
+    pub fn enable_debug_info(&mut self, files: &[SourceFile], optimized: bool) {
+        self.debug = Some(DebugInfo::new(&self.module, files, optimized));
     }
 
     pub fn codegen(&mut self, code: &Stmt) {
@@ -69,8 +187,273 @@ impl<'ctx> Codegen<'ctx> {
             panic!("Code root should be a block");
         };
 
+        // Generic functions may be called before they are defined
         for stmt in stmts {
-            self.codegen_stmt(stmt);
+            if let StmtKind::DefineFunction(function) = &stmt.kind {
+                if !function.params.is_empty() {
+                    let symbol = stmt.symtable.get_value(&function.identifier).unwrap();
+                    self.generics.insert(symbol.id, stmt.clone());
+                }
+            }
+        }
+
+        // Globals are visible in every function, so they are added before the
+        // functions are generated
+        let mut initializers = Vec::new();
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::DefineVariable {
+                    identifier, value, ..
+                }
+                | StmtKind::DefineValue {
+                    identifier, value, ..
+                } => {
+                    if let Some(ptr) = self.codegen_global(stmt, identifier, value) {
+                        initializers.push((ptr, value));
+                    }
+                }
+                StmtKind::DefineConst { identifier, .. } => self.codegen_const(stmt, identifier),
+                _ => (),
+            }
+        }
+
+        for stmt in stmts {
+            if !matches!(
+                stmt.kind,
+                StmtKind::DefineVariable { .. }
+                    | StmtKind::DefineValue { .. }
+                    | StmtKind::DefineConst { .. }
+            ) {
+                self.codegen_stmt(stmt);
+            }
+        }
+
+        // Initializers can call any function of the module
+        for (ptr, value) in initializers {
+            self.codegen_initializer(ptr, value);
+        }
+
+        // Instances can call other generics, adding more instances
+        while let Some(instance) = self.instances.pop() {
+            self.codegen_instance(instance);
+        }
+        self.type_args.clear();
+    }
+
+    /// Add a global for a variable defined at module scope. Literals are
+    /// stored in the global right away, otherwise the global is returned so
+    /// its value can be stored by the initializer.
+    fn codegen_global(
+        &mut self,
+        stmt: &Stmt,
+        identifier: &str,
+        value: &Expr,
+    ) -> Option<PointerValue<'ctx>> {
+        let symbol = stmt.symtable.get_value(identifier).unwrap();
+        let typ = self.type_as_basic_type(symbol.typ.clone());
+
+        let global = self.module.add_global(typ, None, identifier);
+        global.set_linkage(Linkage::Internal);
+
+        let ptr = global.as_pointer_value();
+        self.globals.insert(symbol.id, ptr);
+        self.references.insert(symbol.id, ptr);
+
+        match &value.kind {
+            ExprKind::Literal(
+                literal @ (Literal::Integer(_) | Literal::Float(_) | Literal::Boolean(_)),
+            ) => {
+                global.set_initializer(&self.codegen_value(literal.clone()));
+                global.set_constant(!symbol.mutable);
+                None
+            }
+            _ => {
+                global.set_initializer(&typ.const_zero());
+                Some(ptr)
+            }
+        }
+    }
+
+    /// Add a global for a constant. Uses of constants are folded into their
+    /// value, the global keeps the constant in the output like any other
+    /// definition.
+    fn codegen_const(&mut self, stmt: &Stmt, identifier: &str) {
+        let symbol = stmt.symtable.get_value(identifier).unwrap();
+        let Some(constant) = symbol.constant else {
+            return;
+        };
+
+        let value = match constant {
+            // Strings point into a constant array of their bytes
+            Literal::String(value) => {
+                let bytes = self.context.const_string(value.as_bytes(), true);
+                let data =
+                    self.module
+                        .add_global(bytes.get_type(), None, &format!("{identifier}.str"));
+                data.set_linkage(Linkage::Private);
+                data.set_initializer(&bytes);
+                data.set_constant(true);
+
+                let string_type = self.type_as_basic_type(Type::String).into_pointer_type();
+                data.as_pointer_value()
+                    .const_cast(string_type)
+                    .as_basic_value_enum()
+            }
+            constant => self.codegen_value(constant),
+        };
+
+        let global = self.module.add_global(value.get_type(), None, identifier);
+        global.set_linkage(Linkage::Internal);
+        global.set_initializer(&value);
+        global.set_constant(true);
+    }
+
+    /// Store the value of a global in the function initializing globals. It is
+    /// a static constructor, so it runs before main.
+    fn codegen_initializer(&mut self, ptr: PointerValue<'ctx>, value: &Expr) {
+        let func = match self.initializer.take() {
+            // Continue after the initializers of the previous modules
+            Some(ret) => {
+                let block = ret.get_parent().unwrap();
+                ret.erase_from_basic_block();
+                self.builder.position_at_end(block);
+                block.get_parent().unwrap()
+            }
+            None => {
+                let func = self.add_initializer();
+                let block = self.context.append_basic_block(func, "entrypoint");
+                self.builder.position_at_end(block);
+                func
+            }
+        };
+
+        // The initializer has no debug info, locations of functions must not
+        // leak into it
+        self.builder.unset_current_debug_location();
+        self.references.clone_from(&self.globals);
+        self.current_func = Some(func);
+        self.current_func_void = true;
+
+        let value = self.codegen_expr(value).unwrap();
+        self.builder.build_store(ptr, value);
+        self.initializer = Some(self.builder.build_return(None));
+    }
+
+    /// Declare the function initializing globals and register it as a static
+    /// constructor.
+    fn add_initializer(&mut self) -> FunctionValue<'ctx> {
+        let fn_type = self.context.void_type().fn_type(&[], false);
+        let func = self
+            .module
+            .add_function("sloth.init", fn_type, Some(Linkage::Internal));
+
+        let i32_type = self.context.i32_type();
+        let data_type = self.context.i8_type().ptr_type(AddressSpace::default());
+        let func_ptr = func.as_global_value().as_pointer_value();
+
+        // Entries are the priority, the constructor and the data it
+        // initializes, which is only used to discard unused constructors
+        let entry_type = self.context.struct_type(
+            &[
+                i32_type.into(),
+                func_ptr.get_type().into(),
+                data_type.into(),
+            ],
+            false,
+        );
+        let entry = entry_type.const_named_struct(&[
+            i32_type.const_int(65535, false).into(),
+            func_ptr.into(),
+            data_type.const_null().into(),
+        ]);
+
+        let ctors = self
+            .module
+            .add_global(entry_type.array_type(1), None, "llvm.global_ctors");
+        ctors.set_linkage(Linkage::Appending);
+        ctors.set_initializer(&entry_type.const_array(&[entry]));
+
+        func
+    }
+
+    /// Function generated from a generic function or intrinsic for the types
+    /// of the arguments it is called with, None if the callee isn't generic.
+    /// Only the declaration is generated right away, the body is generated
+    /// after the module.
+    fn instantiate(&mut self, callee: &Expr, args: &[Expr]) -> Option<FunctionValue<'ctx>> {
+        let ExprKind::Identifier(identifier) = &callee.kind else {
+            return None;
+        };
+        let typ = callee.typ.as_ref().unwrap();
+        let Type::Function { inputs, .. } = typ else {
+            return None;
+        };
+        if !typ.is_generic() {
+            return None;
+        }
+
+        // Arguments may depend on the type parameters of the caller
+        let mut type_args = HashMap::new();
+        for (input, arg) in inputs.iter().zip(args) {
+            let arg_type = arg.typ.as_ref().unwrap().substitute(&self.type_args);
+            input.unify(&arg_type, &mut type_args);
+        }
+
+        let symbol = callee.symtable.get_value(identifier).unwrap();
+        let (generic, params) = match self.generics.get(&symbol.id) {
+            Some(Stmt {
+                kind: StmtKind::DefineFunction(function),
+                ..
+            }) => (Generic::Function(symbol.id), function.params.clone()),
+            _ => (Generic::Intrinsic(identifier.clone()), vec!["T".to_owned()]),
+        };
+
+        let name = format!(
+            "{identifier}<{}>",
+            params.iter().map(|it| &type_args[it]).join(", ")
+        );
+        if let Some(function) = self.module.get_function(&name) {
+            return Some(function);
+        }
+
+        let function = self.codegen_function(&name, &typ.substitute(&type_args));
+        self.instances.push(Instance {
+            function,
+            generic,
+            type_args,
+        });
+
+        Some(function)
+    }
+
+    fn codegen_instance(&mut self, instance: Instance<'ctx>) {
+        let function = instance.function;
+        self.type_args = instance.type_args;
+
+        match instance.generic {
+            Generic::Intrinsic(name) => {
+                let typ = self.type_args["T"].clone();
+                match name.as_str() {
+                    "vlen" => self.INTRINSIC_vlen(function, typ),
+                    "vpush" => self.INTRINSIC_vpush(function, typ),
+                    "vpop" => self.INTRINSIC_vpop(function, typ),
+                    "vget" => self.INTRINSIC_vget(function, typ),
+                    "vset" => self.INTRINSIC_vset(function, typ),
+                    _ => panic!("Unknown intrinsic: {name}"),
+                }
+            }
+            Generic::Function(id) => {
+                let stmt = self.generics[&id].clone();
+                let StmtKind::DefineFunction(definition) = &stmt.kind else {
+                    unreachable!("Only functions can be generic");
+                };
+                let FunctionKind::Normal { body } = &definition.kind else {
+                    unreachable!("Foreign functions can't be generic");
+                };
+
+                let typ = stmt.symtable.get_value(&definition.identifier).unwrap().typ;
+                self.codegen_function_body(function, definition, body, &typ, stmt.span);
+            }
         }
     }
 
@@ -87,7 +470,82 @@ impl<'ctx> Codegen<'ctx> {
         builder.build_alloca(typ, name)
     }
 
+    /// Whether the block the builder is positioned at already ends in a
+    /// terminator such as a return, nothing may be added after it.
+    fn is_terminated(&self) -> bool {
+        self.builder
+            .get_insert_block()
+            .and_then(|it| it.get_terminator())
+            .is_some()
+    }
+
+    /// Branch to the block unless the current block was already terminated,
+    /// for example by a return at the end of an if body.
+    fn codegen_branch(&mut self, block: BasicBlock<'ctx>) {
+        if !self.is_terminated() {
+            self.builder.build_unconditional_branch(block);
+        }
+    }
+
+    /// Generate the body of a loop, break and continue inside of it branch to
+    /// the given blocks.
+    fn codegen_loop_body(
+        &mut self,
+        label: &Option<String>,
+        body: &Stmt,
+        next: BasicBlock<'ctx>,
+        exit: BasicBlock<'ctx>,
+    ) {
+        self.loops.push(Loop {
+            label: label.clone(),
+            next,
+            exit,
+        });
+        self.codegen_stmt(body);
+        self.loops.pop();
+    }
+
+    /// Loop a break or continue with the label refers to, the innermost loop
+    /// without a label. Analysis made sure it exists.
+    fn find_loop(&self, label: &Option<String>) -> &Loop<'ctx> {
+        let found = match label {
+            Some(_) => self.loops.iter().rev().find(|it| &it.label == label),
+            None => self.loops.last(),
+        };
+
+        found.expect("Loop control outside of a loop")
+    }
+
+    /// Attach the location of the span to the instructions built from now on,
+    /// does nothing without debug info or outside of functions.
+    fn set_debug_location(&self, span: Span) {
+        if let Some(location) = self
+            .debug
+            .as_ref()
+            .and_then(|it| it.location(self.context, span))
+        {
+            self.builder.set_current_debug_location(location);
+        }
+    }
+
+    /// Describe a variable stored at the pointer in the debug info.
+    fn declare_variable(
+        &self,
+        ptr: PointerValue<'ctx>,
+        name: &str,
+        typ: &Type,
+        span: Span,
+        parameter: Option<u32>,
+    ) {
+        if let (Some(debug), Some(block)) = (&self.debug, self.builder.get_insert_block()) {
+            let typ = typ.substitute(&self.type_args);
+            debug.declare_variable(self.context, ptr, name, &typ, span, parameter, block);
+        }
+    }
+
     fn codegen_stmt(&mut self, code: &Stmt) {
+        self.set_debug_location(code.span);
+
         match &code.kind {
             StmtKind::Block(stmts) => self.codegen_block(stmts),
             StmtKind::ExprStmt(expr) => {
@@ -115,19 +573,23 @@ impl<'ctx> Codegen<'ctx> {
                 // Building the blocks for then
                 self.builder.position_at_end(then_bb);
                 self.codegen_stmt(if_then);
-                self.builder.build_unconditional_branch(continue_bb);
+                self.codegen_branch(continue_bb);
 
                 // Building the blocks for else
                 self.builder.position_at_end(else_bb);
                 if let Some(else_then) = else_then {
                     self.codegen_stmt(else_then);
                 }
-                self.builder.build_unconditional_branch(continue_bb);
+                self.codegen_branch(continue_bb);
 
                 // Position the builder at the end of the continue block
                 self.builder.position_at_end(continue_bb);
             }
-            StmtKind::WhileStmt { condition, body } => {
+            StmtKind::WhileStmt {
+                label,
+                condition,
+                body,
+            } => {
                 // Get the current function
                 let func = self.current_func.unwrap();
 
@@ -145,13 +607,14 @@ impl<'ctx> Codegen<'ctx> {
 
                 // Building the blocks for the body of the loop
                 self.builder.position_at_end(body_bb);
-                self.codegen_stmt(body);
-                self.builder.build_unconditional_branch(loop_bb);
+                self.codegen_loop_body(label, body, loop_bb, after_bb);
+                self.codegen_branch(loop_bb);
 
                 // Position the builder at the end of the loop
                 self.builder.position_at_end(after_bb);
             }
             StmtKind::ForStmt {
+                label,
                 iterator,
                 identifier,
                 body,
@@ -161,6 +624,7 @@ impl<'ctx> Codegen<'ctx> {
 
                 let loop_bb = self.context.append_basic_block(func, "loop");
                 let body_bb = self.context.append_basic_block(func, "loop body");
+                let step_bb = self.context.append_basic_block(func, "loop step");
                 let after_bb = self.context.append_basic_block(func, "after loop");
 
                 // Before the loop
@@ -181,6 +645,7 @@ impl<'ctx> Codegen<'ctx> {
                 let table = body.symtable.clone();
                 let symbol = table.get_value(identifier).unwrap();
                 self.references.insert(symbol.id, current_ptr);
+                self.declare_variable(current_ptr, identifier, &symbol.typ, code.span, None);
 
                 let end_ptr = self
                     .builder
@@ -205,8 +670,12 @@ impl<'ctx> Codegen<'ctx> {
 
                 // Building the blocks for the body of the loop
                 self.builder.position_at_end(body_bb);
-                self.codegen_stmt(body);
+                self.codegen_loop_body(label, body, step_bb, after_bb);
+                self.codegen_branch(step_bb);
 
+                // Building the block stepping to the next element, continue
+                // branches here so the element isn't repeated
+                self.builder.position_at_end(step_bb);
                 let current = self.builder.build_load(i32_type, current_ptr, "");
                 let updated_current = self.builder.build_int_add(
                     current.into_int_value(),
@@ -214,23 +683,50 @@ impl<'ctx> Codegen<'ctx> {
                     "",
                 );
                 self.builder.build_store(current_ptr, updated_current);
-
                 self.builder.build_unconditional_branch(loop_bb);
 
                 // Position the builder at the end of the loop
                 self.builder.position_at_end(after_bb);
             }
+            StmtKind::Loop { label, body } => {
+                // Get the current function
+                let func = self.current_func.unwrap();
+
+                let body_bb = self.context.append_basic_block(func, "loop body");
+                let after_bb = self.context.append_basic_block(func, "after loop");
+
+                self.builder.build_unconditional_branch(body_bb);
+
+                // Building the blocks for the body of the loop
+                self.builder.position_at_end(body_bb);
+                self.codegen_loop_body(label, body, body_bb, after_bb);
+                self.codegen_branch(body_bb);
+
+                // Position the builder at the end of the loop, it has no
+                // predecessors if the loop is never broken out of
+                self.builder.position_at_end(after_bb);
+            }
+            StmtKind::Break(label) => {
+                let exit = self.find_loop(label).exit;
+                self.builder.build_unconditional_branch(exit);
+            }
+            StmtKind::Continue(label) => {
+                let next = self.find_loop(label).next;
+                self.builder.build_unconditional_branch(next);
+            }
             StmtKind::DefineVariable {
                 identifier, value, ..
             } => {
                 let table = code.symtable.clone();
                 let symbol = table.get_value(identifier).unwrap();
 
-                let ptr = self.codegen_alloca(self.type_as_basic_type(symbol.typ), identifier);
+                let ptr =
+                    self.codegen_alloca(self.type_as_basic_type(symbol.typ.clone()), identifier);
                 let init_value = self.codegen_expr(value).unwrap();
 
                 self.builder.build_store(ptr, init_value);
                 self.references.insert(symbol.id, ptr);
+                self.declare_variable(ptr, identifier, &symbol.typ, code.span, None);
             }
             StmtKind::DefineValue {
                 identifier, value, ..
@@ -238,239 +734,241 @@ impl<'ctx> Codegen<'ctx> {
                 let table = code.symtable.clone();
                 let symbol = table.get_value(identifier).unwrap();
 
-                let ptr = self.codegen_alloca(self.type_as_basic_type(symbol.typ), identifier);
+                let ptr =
+                    self.codegen_alloca(self.type_as_basic_type(symbol.typ.clone()), identifier);
                 let init_value = self.codegen_expr(value).unwrap();
 
                 self.builder.build_store(ptr, init_value);
                 self.references.insert(symbol.id, ptr);
+                self.declare_variable(ptr, identifier, &symbol.typ, code.span, None);
             }
-            StmtKind::AssignVariable { identifier, value } => {
-                let table = code.symtable.clone();
-                let symbol = table.get_value(identifier).unwrap();
-                if symbol.mutable {
-                    let ptr = self.references.get(&symbol.id).unwrap();
-                    let init_value = self.codegen_expr(value).unwrap();
-
-                    self.builder.build_store(*ptr, init_value);
-                } else {
-                    panic!("Val not mutable!");
+            StmtKind::Assign { target, op, value } => {
+                let typ = target.typ.clone().unwrap();
+                let ptr = self.codegen_place(target);
+
+                // Compound assignments load the current value to apply their
+                // operator to it
+                let current = op.map(|_| {
+                    self.builder
+                        .build_load(self.type_as_basic_type(typ.clone()), ptr, "")
+                });
+
+                let mut value = self.codegen_expr(value).unwrap();
+                if let (Some(op), Some(current)) = (op, current) {
+                    value = self.codegen_binary(*op, &typ, current, value);
                 }
+
+                self.builder.build_store(ptr, value);
             }
             StmtKind::DefineFunction(function) => {
+                // Generic functions are generated once the types they are
+                // called with are known
+                if !function.params.is_empty() {
+                    return;
+                }
+
                 let table = code.symtable.clone();
-                self.codegen_function(table, function.clone());
+                let typ = table.get_value(&function.identifier).unwrap().typ;
+                let func = self.codegen_function(&function.identifier, &typ);
 
                 // If the function is written in sloth (as opposed to an extern one) we generate
                 // the block contents
                 if let FunctionKind::Normal { body } = &function.kind {
-                    if let StmtKind::Block(code) = &body.kind {
-                        // Make the block containing the code for the function
-                        let func = self.current_func.unwrap();
-                        let block = self.context.append_basic_block(func, "entrypoint");
-
-                        // Position the builder to be at the block
-                        self.builder.position_at_end(block);
-
-                        // FIXME 🍝
-                        // This code adds parameters to the body so they're accessible
-                        let body_table = body.symtable.clone();
-                        for (i, input) in function.inputs.iter().enumerate() {
-                            let symbol = body_table.get_value(&input.identifier).unwrap();
-
-                            let ptr = self.codegen_alloca(
-                                self.type_as_basic_type(symbol.typ),
-                                &input.identifier,
-                            );
-                            let init_value = func.get_nth_param(i as u32).unwrap();
-
-                            self.builder.build_store(ptr, init_value);
-                            self.references.insert(symbol.id, ptr);
-                        }
-
-                        // Codegen the function body
-                        self.codegen_block(code);
-
-                        if self.current_func_void {
-                            self.builder.build_return(None);
-                        }
-                    }
-                };
+                    self.codegen_function_body(func, function, body, &typ, code.span);
+                }
             }
-            StmtKind::Return(expr) => {
+            StmtKind::Return(Some(expr)) => {
                 let res = self.codegen_expr(expr).unwrap();
                 self.builder.build_return(Some(&res));
             }
+            StmtKind::Return(None) => {
+                self.builder.build_return(None);
+            }
+            StmtKind::Match { value, arms } => {
+                let typ = value.typ.clone().unwrap();
+                let (ptr, blocks, after_bb) =
+                    self.codegen_match(value, arms.iter().map(|it| &it.pattern));
+
+                for (arm, block) in arms.iter().zip(blocks) {
+                    let Some(block) = block else { continue };
+
+                    self.builder.position_at_end(block);
+                    let table = arm.body.symtable.clone();
+                    self.codegen_pattern(&arm.pattern, &table, &typ, ptr, arm.pattern_span);
+                    self.codegen_stmt(&arm.body);
+                    self.codegen_branch(after_bb);
+                }
+
+                self.builder.position_at_end(after_bb);
+            }
+            // Uses of constants are replaced by their value
+            StmtKind::DefineConst { .. } => (),
+            StmtKind::DefineStruct(_) | StmtKind::DefineEnum(_) | StmtKind::Use(_) => (),
         }
     }
 
-    fn codegen_function(&mut self, table: SymbolTable, function: Function) -> FunctionValue {
-        // Clear references to variables
-        self.references.clear();
+    /// Declare a function with the signature of the function type.
+    fn codegen_function(&mut self, name: &str, typ: &Type) -> FunctionValue<'ctx> {
+        let Type::Function { inputs, output } = typ.substitute(&self.type_args) else {
+            panic!("Function symbol should have a function type");
+        };
 
-        let inputs = function.inputs;
         let inputs_typ = inputs
-            .iter()
-            .map(|it| table.get_type(&it.typ).unwrap())
+            .into_iter()
             .map(|it| self.type_as_metadata_type(it))
             .collect_vec();
 
-        let output = function.output;
-        let output_typ = output
-            .map(|it| table.get_type(&it))
-            .unwrap_or(Some(Type::Void))
-            .unwrap();
-
-        let llvm_function_type = match output_typ {
+        let llvm_function_type = match *output {
             Type::Void => self.context.void_type().fn_type(&inputs_typ, false),
-            Type::Integer => self.context.i32_type().fn_type(&inputs_typ, false),
-            Type::Float => self.context.f32_type().fn_type(&inputs_typ, false),
-            Type::Boolean => self.context.bool_type().fn_type(&inputs_typ, false),
-            Type::Array { ref typ, .. } => {
-                let i32_type = self.context.i32_type().as_basic_type_enum();
+            output => self.type_as_basic_type(output).fn_type(&inputs_typ, false),
+        };
 
-                let typ = self
-                    .type_as_basic_type(*typ.clone())
-                    .ptr_type(AddressSpace::default())
-                    .as_basic_type_enum();
+        self.module.add_function(name, llvm_function_type, None)
+    }
 
-                let vector_type = self.context.struct_type(&[i32_type, i32_type, typ], false);
-                let ptr_to_that = vector_type.ptr_type(AddressSpace::default());
+    /// Generate the body of a function declared with `codegen_function`.
+    fn codegen_function_body(
+        &mut self,
+        func: FunctionValue<'ctx>,
+        function: &Function,
+        body: &Stmt,
+        typ: &Type,
+        span: Span,
+    ) {
+        let Type::Function { inputs, output } = typ.substitute(&self.type_args) else {
+            panic!("Function symbol should have a function type");
+        };
+
+        // Only globals are visible in a new function
+        self.references.clone_from(&self.globals);
+
+        self.current_func = Some(func);
+        self.current_func_void = *output == Type::Void;
+
+        if let StmtKind::Block(code) = &body.kind {
+            // Make the block containing the code for the function
+            let block = self.context.append_basic_block(func, "entrypoint");
 
-                ptr_to_that.fn_type(&inputs_typ, false)
+            // Position the builder to be at the block
+            self.builder.position_at_end(block);
+
+            if let Some(debug) = &mut self.debug {
+                let name = func.get_name().to_string_lossy();
+                debug.start_function(func, &name, span, &inputs, &output);
             }
-            Type::String => {
-                let i8_type = self.context.i8_type().as_basic_type_enum();
-                let ptr_type = i8_type
-                    .ptr_type(AddressSpace::default())
-                    .as_basic_type_enum();
+            self.set_debug_location(span);
+
+            // FIXME 🍝
+            // This code adds parameters to the body so they're accessible
+            let body_table = body.symtable.clone();
+            for (i, input) in function.inputs.iter().enumerate() {
+                let symbol = body_table.get_value(&input.identifier).unwrap();
+
+                let ptr = self.codegen_alloca(
+                    self.type_as_basic_type(symbol.typ.clone()),
+                    &input.identifier,
+                );
+                let init_value = func.get_nth_param(i as u32).unwrap();
+
+                self.builder.build_store(ptr, init_value);
+                self.references.insert(symbol.id, ptr);
 
-                ptr_type.fn_type(&inputs_typ, false)
+                let number = Some(i as u32 + 1);
+                let name = &input.identifier;
+                self.declare_variable(ptr, name, &symbol.typ, span, number);
             }
-            _ => todo!(),
-        };
 
-        let llvm_function =
-            self.module
-                .add_function(&function.identifier, llvm_function_type, None);
+            // Codegen the function body
+            self.codegen_block(code);
 
-        self.current_func = Some(llvm_function);
-        self.current_func_void = matches!(output_typ, Type::Void);
+            // Analysis made sure functions with an output return on every path,
+            // so the end of their body is only reached after an endless loop
+            if !self.is_terminated() {
+                if self.current_func_void {
+                    self.builder.build_return(None);
+                } else {
+                    self.builder.build_unreachable();
+                }
+            }
 
-        llvm_function
+            // Locations of this function must not leak into others
+            self.builder.unset_current_debug_location();
+            if let Some(debug) = &mut self.debug {
+                debug.finish_function();
+            }
+        }
     }
 
     fn codegen_block(&mut self, code: &[Stmt]) {
         for stmt in code {
+            // Statements after a return, break or continue can never run
+            if self.is_terminated() {
+                break;
+            }
+
             self.codegen_stmt(stmt);
         }
     }
 
-    fn codegen_expr(&self, code: &Expr) -> Option<BasicValueEnum<'ctx>> {
+    fn codegen_expr(&mut self, code: &Expr) -> Option<BasicValueEnum<'ctx>> {
         Some(match &code.kind {
             ExprKind::Literal(literal) => self.codegen_value(literal.clone()),
             ExprKind::Grouping(inner) => self.codegen_expr(inner)?,
             ExprKind::Identifier(ident) => {
                 let table = code.symtable.clone();
                 let symbol = table.get_value(ident).unwrap();
+
+                // Constants used before their definition weren't folded yet
+                if let Some(constant) = symbol.constant {
+                    return Some(self.codegen_value(constant));
+                }
+
                 let ptr = self.references.get(&symbol.id).unwrap();
 
                 self.builder
                     .build_load(self.type_as_basic_type(symbol.typ.clone()), *ptr, "")
             }
-            ExprKind::BinaryOp { op, lhs, rhs } => match lhs.typ {
-                Some(Type::Integer) | Some(Type::Boolean) => {
-                    use IntPredicate::*;
-
-                    let l = self.codegen_expr(lhs).unwrap().into_int_value();
-                    let r = self.codegen_expr(rhs).unwrap().into_int_value();
-
-                    match op {
-                        BinaryOp::Add => self.builder.build_int_add(l, r, "add").into(),
-                        BinaryOp::Sub => self.builder.build_int_sub(l, r, "sub").into(),
-                        BinaryOp::Mul => self.builder.build_int_mul(l, r, "mul").into(),
-                        BinaryOp::Div => self.builder.build_int_signed_div(l, r, "div").into(),
-                        BinaryOp::Mod => self.builder.build_int_signed_rem(l, r, "mod").into(),
-
-                        BinaryOp::Gt => self.builder.build_int_compare(SGT, l, r, "gt").into(),
-                        BinaryOp::GtEq => self.builder.build_int_compare(SGE, l, r, "").into(),
-                        BinaryOp::Lt => self.builder.build_int_compare(SLT, l, r, "lt").into(),
-                        BinaryOp::LtEq => self.builder.build_int_compare(SLE, l, r, "").into(),
+            ExprKind::BinaryOp { op, lhs, rhs } => {
+                let typ = lhs.typ.as_ref();
+                let typ = typ.expect("Critical Error: Type should never be null by this point");
 
-                        BinaryOp::EqEq => self.builder.build_int_compare(EQ, l, r, "").into(),
-                        BinaryOp::NotEq => self.builder.build_int_compare(NE, l, r, "").into(),
-
-                        BinaryOp::LogicalAnd => self.builder.build_and(l, r, "logand").into(),
-                        BinaryOp::LogicalOr => self.builder.build_or(l, r, "logor").into(),
-
-                        BinaryOp::Range => {
-                            // FIXME: Change Range type to Iterator type
-                            self.context
-                                .const_struct(&[l.into(), r.into()], false)
-                                .into()
-                        }
-
-                        _ => panic!("{op:?}"),
-                    }
-                }
-                Some(Type::Float) => {
-                    use FloatPredicate::*;
-
-                    let l = self.codegen_expr(lhs).unwrap().into_float_value();
-                    let r = self.codegen_expr(rhs).unwrap().into_float_value();
-
-                    match op {
-                        BinaryOp::Add => self.builder.build_float_add(l, r, "add").into(),
-                        BinaryOp::Sub => self.builder.build_float_sub(l, r, "sub").into(),
-                        BinaryOp::Mul => self.builder.build_float_mul(l, r, "mul").into(),
-                        BinaryOp::Div => self.builder.build_float_div(l, r, "div").into(),
-                        BinaryOp::Mod => self.builder.build_float_rem(l, r, "mod").into(),
-
-                        BinaryOp::Gt => self.builder.build_float_compare(OGT, l, r, "gt").into(),
-                        BinaryOp::GtEq => self.builder.build_float_compare(OGE, l, r, "gt").into(),
-                        BinaryOp::Lt => self.builder.build_float_compare(OLT, l, r, "lt").into(),
-                        BinaryOp::LtEq => self.builder.build_float_compare(OLE, l, r, "le").into(),
-
-                        BinaryOp::EqEq => self.builder.build_float_compare(OEQ, l, r, "eq").into(),
-                        BinaryOp::NotEq => self.builder.build_float_compare(ONE, l, r, "ne").into(),
-                        _ => panic!(),
-                    }
-                }
-                None => unreachable!("Critical Error: Type should never be null by this point"),
-                _ => todo!(),
-            },
+                let l = self.codegen_expr(lhs).unwrap();
+                let r = self.codegen_expr(rhs).unwrap();
+                self.codegen_binary(*op, typ, l, r)
+            }
+            ExprKind::UnaryOp {
+                op: UnaryOp::Reference,
+                value,
+            } => self.codegen_place(value).into(),
+            ExprKind::UnaryOp {
+                op: UnaryOp::Dereference,
+                ..
+            }
+            | ExprKind::Index { .. } => {
+                let typ = self.type_as_basic_type(code.typ.clone().unwrap());
+                let ptr = self.codegen_place(code);
+                self.builder.build_load(typ, ptr, "")
+            }
+            // References and dereferences are handled above as they work on
+            // places instead of values
             ExprKind::UnaryOp { op, value } => {
-                //
-                match value.typ {
-                    Some(Type::Boolean) => {
-                        let _value = self.codegen_expr(value).unwrap().into_int_value();
-
-                        match op {
-                            UnaryOp::Not => {
-                                todo!()
-                            }
-                            UnaryOp::Neg => todo!(),
-                        }
-                    }
-                    Some(Type::Integer) => {
-                        let value = self.codegen_expr(value).unwrap().into_int_value();
+                let typ = value.typ.as_ref();
+                let typ = typ.expect("Critical Error: Type should never be null by this point");
+                let value = self.codegen_expr(value).unwrap();
 
-                        match op {
-                            UnaryOp::Not => todo!(),
-                            UnaryOp::Neg => self.builder.build_int_neg(value, "neg").into(),
-                        }
-                    }
-                    Some(Type::Float) => {
-                        let value = self.codegen_expr(value).unwrap().into_float_value();
-
-                        match op {
-                            UnaryOp::Not => todo!(),
-                            UnaryOp::Neg => self.builder.build_float_neg(value, "neg").into(),
-                        }
+                match (op, typ) {
+                    (UnaryOp::Not, Type::Boolean) => {
+                        self.builder.build_not(value.into_int_value(), "not").into()
                     }
-                    None => {
-                        unreachable!("Critical Error: Type should never be null by this point!")
-                    }
-                    _ => todo!(),
+                    (UnaryOp::Neg, Type::Integer) => self
+                        .builder
+                        .build_int_neg(value.into_int_value(), "neg")
+                        .into(),
+                    (UnaryOp::Neg, Type::Float) => self
+                        .builder
+                        .build_float_neg(value.into_float_value(), "neg")
+                        .into(),
+                    _ => unreachable!("'{op}' on {typ} is rejected during analysis"),
                 }
             }
             ExprKind::Call { callee, args } => {
@@ -478,10 +976,13 @@ impl<'ctx> Codegen<'ctx> {
                 // extracting an identifier to it. Change this
                 // so you can do for example `fn(){}()`.
                 let ExprKind::Identifier(ident) = &callee.kind else { panic!() };
-                let function = self
-                    .module
-                    .get_function(ident)
-                    .unwrap_or_else(|| panic!("Function not found: {}", ident));
+                let function = match self.instantiate(callee, args) {
+                    Some(function) => function,
+                    None => self
+                        .module
+                        .get_function(ident)
+                        .unwrap_or_else(|| panic!("Function not found: {}", ident)),
+                };
 
                 let args = args
                     .iter()
@@ -489,6 +990,10 @@ impl<'ctx> Codegen<'ctx> {
                     .map(|arg| arg.unwrap().into())
                     .collect::<Vec<BasicMetadataValueEnum>>();
 
+                // Calls point at themselves so nested calls show up correctly
+                // in backtraces
+                self.set_debug_location(code.span);
+
                 let call = self
                     .builder
                     .build_call(function, &args, "")
@@ -499,10 +1004,556 @@ impl<'ctx> Codegen<'ctx> {
                     Either::Right(_) => return None,
                 }
             }
+            ExprKind::StructLiteral { fields, .. } => {
+                // Fields can be written in any order, they are inserted at the
+                // position they were declared at
+                let typ = code.typ.clone().unwrap();
+                let mut value = self
+                    .type_as_basic_type(typ.clone())
+                    .into_struct_type()
+                    .get_undef();
+
+                for (field, field_value) in fields {
+                    let (index, _) = typ.field(field).unwrap();
+                    let field_value = self.codegen_expr(field_value).unwrap();
+                    value = self
+                        .builder
+                        .build_insert_value(value, field_value, index as u32, "")
+                        .unwrap()
+                        .into_struct_value();
+                }
+
+                value.as_basic_value_enum()
+            }
+            ExprKind::FieldAccess { value, field } => {
+                let (index, _) = value.typ.as_ref().unwrap().field(field).unwrap();
+                let value = self.codegen_expr(value).unwrap().into_struct_value();
+
+                self.builder
+                    .build_extract_value(value, index as u32, field)
+                    .unwrap()
+            }
+            ExprKind::Variant { variant, args, .. } => {
+                // The variant is built up in memory as its fields are stored
+                // in the payload through a pointer to a struct of them
+                let typ = code.typ.clone().unwrap();
+                let (tag, fields) = typ.variant(variant).unwrap();
+                let enum_type = self.type_as_basic_type(typ.clone());
+                let variant_type = self.variant_type(fields);
+
+                let ptr = self.codegen_alloca(enum_type, variant);
+                let tag_ptr = self
+                    .builder
+                    .build_struct_gep(enum_type, ptr, 0, "tag")
+                    .unwrap();
+                self.builder.build_store(
+                    tag_ptr,
+                    self.context.i32_type().const_int(tag as u64, false),
+                );
+
+                let payload_ptr = self.payload_ptr(enum_type, variant_type, ptr);
+                for (index, arg) in args.iter().enumerate() {
+                    let value = self.codegen_expr(arg).unwrap();
+                    let field_ptr = self
+                        .builder
+                        .build_struct_gep(variant_type, payload_ptr, index as u32, "")
+                        .unwrap();
+                    self.builder.build_store(field_ptr, value);
+                }
+
+                self.builder.build_load(enum_type, ptr, variant)
+            }
+            ExprKind::Match { value, arms } => {
+                let typ = value.typ.clone().unwrap();
+
+                // Arms store their value in a slot which is loaded after the
+                // match, matches evaluating to Void have no value
+                let result_type = code.typ.clone().unwrap();
+                let result_type =
+                    (result_type != Type::Void).then(|| self.type_as_basic_type(result_type));
+                let result = result_type.map(|it| self.codegen_alloca(it, "match"));
+
+                let (ptr, blocks, after_bb) =
+                    self.codegen_match(value, arms.iter().map(|it| &it.pattern));
+
+                for (arm, block) in arms.iter().zip(blocks) {
+                    let Some(block) = block else { continue };
+
+                    self.builder.position_at_end(block);
+                    let table = arm.body.symtable.clone();
+                    self.codegen_pattern(&arm.pattern, &table, &typ, ptr, arm.pattern_span);
+
+                    let value = self.codegen_expr(&arm.body);
+                    if let (Some(result), Some(value)) = (result, value) {
+                        self.builder.build_store(result, value);
+                    }
+                    self.codegen_branch(after_bb);
+                }
+
+                self.builder.position_at_end(after_bb);
+
+                match (result_type, result) {
+                    (Some(typ), Some(ptr)) => self.builder.build_load(typ, ptr, ""),
+                    _ => return None,
+                }
+            }
         })
     }
 
-    fn codegen_value(&self, value: Literal) -> BasicValueEnum<'ctx> {
+    /// Apply the operator to two values of the type, used both by binary
+    /// expressions and by compound assignments.
+    fn codegen_binary(
+        &mut self,
+        op: BinaryOp,
+        typ: &Type,
+        l: BasicValueEnum<'ctx>,
+        r: BasicValueEnum<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        match typ {
+            Type::Integer | Type::Boolean => {
+                use IntPredicate::*;
+
+                let l = l.into_int_value();
+                let r = r.into_int_value();
+
+                match op {
+                    BinaryOp::Add => self.builder.build_int_add(l, r, "add").into(),
+                    BinaryOp::Sub => self.builder.build_int_sub(l, r, "sub").into(),
+                    BinaryOp::Mul => self.builder.build_int_mul(l, r, "mul").into(),
+                    BinaryOp::Div => self.builder.build_int_signed_div(l, r, "div").into(),
+                    BinaryOp::Mod => self.builder.build_int_signed_rem(l, r, "mod").into(),
+                    BinaryOp::Pow => self.codegen_int_pow(l, r).into(),
+
+                    // Shifting by the width of the integer or more is undefined
+                    // in LLVM, so only the low bits of the amount are used
+                    BinaryOp::Shl | BinaryOp::Shr => {
+                        let mask = l.get_type().const_int(31, false);
+                        let r = self.builder.build_and(r, mask, "amount");
+
+                        match op {
+                            BinaryOp::Shl => self.builder.build_left_shift(l, r, "shl").into(),
+                            _ => self.builder.build_right_shift(l, r, true, "shr").into(),
+                        }
+                    }
+
+                    BinaryOp::Gt => self.builder.build_int_compare(SGT, l, r, "gt").into(),
+                    BinaryOp::GtEq => self.builder.build_int_compare(SGE, l, r, "").into(),
+                    BinaryOp::Lt => self.builder.build_int_compare(SLT, l, r, "lt").into(),
+                    BinaryOp::LtEq => self.builder.build_int_compare(SLE, l, r, "").into(),
+
+                    BinaryOp::EqEq => self.builder.build_int_compare(EQ, l, r, "").into(),
+                    BinaryOp::NotEq => self.builder.build_int_compare(NE, l, r, "").into(),
+
+                    BinaryOp::LogicalAnd => self.builder.build_and(l, r, "logand").into(),
+                    BinaryOp::LogicalOr => self.builder.build_or(l, r, "logor").into(),
+
+                    BinaryOp::Range => {
+                        // FIXME: Change Range type to Iterator type
+                        // The bounds aren't necessarily constants, so the
+                        // range is built up from an undefined struct
+                        let i32_type = self.context.i32_type();
+                        let range = self
+                            .context
+                            .struct_type(&[i32_type.into(), i32_type.into()], false)
+                            .get_undef();
+                        let range = self.builder.build_insert_value(range, l, 0, "").unwrap();
+                        self.builder
+                            .build_insert_value(range, r, 1, "range")
+                            .unwrap()
+                            .as_basic_value_enum()
+                    }
+
+                    _ => panic!("{op:?}"),
+                }
+            }
+            Type::Float => {
+                use FloatPredicate::*;
+
+                let l = l.into_float_value();
+                let r = r.into_float_value();
+
+                match op {
+                    BinaryOp::Add => self.builder.build_float_add(l, r, "add").into(),
+                    BinaryOp::Sub => self.builder.build_float_sub(l, r, "sub").into(),
+                    BinaryOp::Mul => self.builder.build_float_mul(l, r, "mul").into(),
+                    BinaryOp::Div => self.builder.build_float_div(l, r, "div").into(),
+                    BinaryOp::Mod => self.builder.build_float_rem(l, r, "mod").into(),
+                    BinaryOp::Pow => {
+                        let pow = Intrinsic::find("llvm.pow").unwrap();
+                        let function = pow
+                            .get_declaration(&self.module, &[l.get_type().into()])
+                            .unwrap();
+
+                        self.builder
+                            .build_call(function, &[l.into(), r.into()], "pow")
+                            .try_as_basic_value()
+                            .left()
+                            .unwrap()
+                    }
+
+                    BinaryOp::Gt => self.builder.build_float_compare(OGT, l, r, "gt").into(),
+                    BinaryOp::GtEq => self.builder.build_float_compare(OGE, l, r, "gt").into(),
+                    BinaryOp::Lt => self.builder.build_float_compare(OLT, l, r, "lt").into(),
+                    BinaryOp::LtEq => self.builder.build_float_compare(OLE, l, r, "le").into(),
+
+                    BinaryOp::EqEq => self.builder.build_float_compare(OEQ, l, r, "eq").into(),
+                    BinaryOp::NotEq => self.builder.build_float_compare(ONE, l, r, "ne").into(),
+                    _ => panic!(),
+                }
+            }
+            // Strings are concatenated by the runtime into a newly allocated
+            // string
+            Type::String if op == BinaryOp::Con => {
+                let ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+                let fn_type = ptr_type.fn_type(&[ptr_type.into(), ptr_type.into()], false);
+                let function = self
+                    .module
+                    .get_function("sconcat")
+                    .unwrap_or_else(|| self.module.add_function("sconcat", fn_type, None));
+
+                self.builder
+                    .build_call(function, &[l.into(), r.into()], "concat")
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+            }
+            _ => unreachable!("'{op}' on {typ} is rejected during analysis"),
+        }
+    }
+
+    /// Raise an integer to a power by multiplying it in once for every step of
+    /// the exponent, negative exponents multiply the base in zero times.
+    fn codegen_int_pow(
+        &mut self,
+        base: IntValue<'ctx>,
+        exponent: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let i32_type = self.context.i32_type();
+
+        let func = self.current_func.unwrap();
+        let start_bb = self.builder.get_insert_block().unwrap();
+        let loop_bb = self.context.append_basic_block(func, "pow");
+        let after_bb = self.context.append_basic_block(func, "after pow");
+
+        self.builder.build_unconditional_branch(loop_bb);
+        self.builder.position_at_end(loop_bb);
+
+        let result = self.builder.build_phi(i32_type, "result");
+        let remaining = self.builder.build_phi(i32_type, "remaining");
+        let result_value = result.as_basic_value().into_int_value();
+        let remaining_value = remaining.as_basic_value().into_int_value();
+
+        let done = self.builder.build_int_compare(
+            IntPredicate::SLE,
+            remaining_value,
+            i32_type.const_zero(),
+            "done",
+        );
+        let next_result = self.builder.build_int_mul(result_value, base, "");
+        let next_remaining =
+            self.builder
+                .build_int_sub(remaining_value, i32_type.const_int(1, false), "");
+        self.builder
+            .build_conditional_branch(done, after_bb, loop_bb);
+
+        result.add_incoming(&[
+            (&i32_type.const_int(1, false), start_bb),
+            (&next_result, loop_bb),
+        ]);
+        remaining.add_incoming(&[(&exponent, start_bb), (&next_remaining, loop_bb)]);
+
+        self.builder.position_at_end(after_bb);
+        result_value
+    }
+
+    /// Store the matched value and branch to the block of the first arm whose
+    /// pattern matches it. Returns the pointer to the stored value, the block
+    /// of every arm and the block after the match. Arms which can never be
+    /// reached as an earlier arm already matches their values get no block.
+    #[allow(clippy::type_complexity)]
+    fn codegen_match<'a>(
+        &mut self,
+        value: &Expr,
+        patterns: impl Iterator<Item = &'a Pattern>,
+    ) -> (
+        PointerValue<'ctx>,
+        Vec<Option<BasicBlock<'ctx>>>,
+        BasicBlock<'ctx>,
+    ) {
+        let func = self.current_func.unwrap();
+        let i32_type = self.context.i32_type();
+
+        let typ = value.typ.clone().unwrap();
+        let enum_type = self.type_as_basic_type(typ.clone());
+
+        let matched = self.codegen_expr(value).unwrap();
+        let ptr = self.codegen_alloca(enum_type, "matched");
+        self.builder.build_store(ptr, matched);
+
+        let tag_ptr = self
+            .builder
+            .build_struct_gep(enum_type, ptr, 0, "tag")
+            .unwrap();
+        let tag = self.builder.build_load(i32_type, tag_ptr, "tag");
+
+        let mut cases = Vec::new();
+        let mut blocks = Vec::new();
+        let mut default = None;
+        for pattern in patterns {
+            // Nothing gets past a wildcard
+            if default.is_some() {
+                blocks.push(None);
+                continue;
+            }
+
+            match pattern {
+                Pattern::Wildcard => {
+                    let block = self.context.append_basic_block(func, "match arm");
+                    default = Some(block);
+                    blocks.push(Some(block));
+                }
+                Pattern::Variant { variant, .. } => {
+                    let (tag, _) = typ.variant(variant).unwrap();
+                    let tag = i32_type.const_int(tag as u64, false);
+                    if cases.iter().any(|(it, _)| *it == tag) {
+                        blocks.push(None);
+                        continue;
+                    }
+
+                    let block = self.context.append_basic_block(func, "match arm");
+                    cases.push((tag, block));
+                    blocks.push(Some(block));
+                }
+            }
+        }
+
+        let after_bb = self.context.append_basic_block(func, "after match");
+
+        // Analysis made sure every variant is covered without a wildcard
+        let default = default.unwrap_or_else(|| {
+            let current = self.builder.get_insert_block().unwrap();
+            let block = self.context.append_basic_block(func, "no match");
+            self.builder.position_at_end(block);
+            self.builder.build_unreachable();
+            self.builder.position_at_end(current);
+            block
+        });
+
+        self.builder
+            .build_switch(tag.into_int_value(), default, &cases);
+
+        (ptr, blocks, after_bb)
+    }
+
+    /// Make the fields bound by the pattern accessible to the body of its arm,
+    /// the bindings point into the matched value stored at the pointer.
+    fn codegen_pattern(
+        &mut self,
+        pattern: &Pattern,
+        table: &SymbolTable,
+        typ: &Type,
+        ptr: PointerValue<'ctx>,
+        span: Span,
+    ) {
+        let Pattern::Variant {
+            variant, bindings, ..
+        } = pattern
+        else {
+            return;
+        };
+
+        let (_, fields) = typ.variant(variant).unwrap();
+        let enum_type = self.type_as_basic_type(typ.clone());
+        let variant_type = self.variant_type(fields);
+        let payload_ptr = self.payload_ptr(enum_type, variant_type, ptr);
+
+        for (index, binding) in bindings.iter().enumerate() {
+            if binding == "_" {
+                continue;
+            }
+
+            let symbol = table.get_value(binding).unwrap();
+            let field_ptr = self
+                .builder
+                .build_struct_gep(variant_type, payload_ptr, index as u32, binding)
+                .unwrap();
+
+            self.references.insert(symbol.id, field_ptr);
+            self.declare_variable(field_ptr, binding, &symbol.typ, span, None);
+        }
+    }
+
+    /// Pointer to the payload of the enum stored at the pointer, as a pointer
+    /// to the struct of fields of one of its variants.
+    fn payload_ptr(
+        &self,
+        enum_type: BasicTypeEnum<'ctx>,
+        variant_type: StructType<'ctx>,
+        ptr: PointerValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let payload_ptr = self
+            .builder
+            .build_struct_gep(enum_type, ptr, 1, "payload")
+            .unwrap();
+
+        self.builder.build_pointer_cast(
+            payload_ptr,
+            variant_type.ptr_type(AddressSpace::default()),
+            "",
+        )
+    }
+
+    /// Pointer to the location an expression refers to, the expression has to
+    /// be a place such as a variable or one of its fields.
+    fn codegen_place(&mut self, code: &Expr) -> PointerValue<'ctx> {
+        match &code.kind {
+            ExprKind::Identifier(ident) => {
+                let table = code.symtable.clone();
+                let symbol = table.get_value(ident).unwrap();
+                *self.references.get(&symbol.id).unwrap()
+            }
+            ExprKind::FieldAccess { value, field } => {
+                let typ = value.typ.clone().unwrap();
+                let (index, _) = typ.field(field).unwrap();
+
+                let ptr = self.codegen_place(value);
+                self.builder
+                    .build_struct_gep(self.type_as_basic_type(typ), ptr, index as u32, field)
+                    .unwrap()
+            }
+            // Arrays point to their elements, so the array itself doesn't have
+            // to be a place
+            ExprKind::Index { value, index } => {
+                let element_type = self.type_as_basic_type(code.typ.clone().unwrap());
+
+                let vector_ptr = self.codegen_expr(value).unwrap().into_pointer_value();
+                let index = self.codegen_expr(index).unwrap().into_int_value();
+
+                let (size_ptr, cap_ptr, inner_ptr) = self._get_ptrs(element_type, vector_ptr);
+                let (size, _, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
+
+                self.codegen_bounds_check(index, size);
+                unsafe {
+                    self.builder
+                        .build_gep(element_type, inner, &[index], "elementptr")
+                }
+            }
+            ExprKind::UnaryOp {
+                op: UnaryOp::Dereference,
+                value,
+            } => self.codegen_expr(value).unwrap().into_pointer_value(),
+            _ => unreachable!("Only places can be assigned to"),
+        }
+    }
+
+    /// Call the panic handler of the runtime unless the index is within the
+    /// size of an array, does nothing if bounds checks are disabled.
+    fn codegen_bounds_check(&mut self, index: IntValue<'ctx>, size: IntValue<'ctx>) {
+        if !self.bounds_checks {
+            return;
+        }
+
+        let func = self.current_func.unwrap();
+        let panic_bb = self.context.append_basic_block(func, "out of bounds");
+        let after_bb = self.context.append_basic_block(func, "in bounds");
+
+        // Negative indices are huge when compared unsigned, so one comparison
+        // covers both ends
+        let in_bounds = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, index, size, "inbounds");
+        self.builder
+            .build_conditional_branch(in_bounds, after_bb, panic_bb);
+
+        self.builder.position_at_end(panic_bb);
+        let i32_type = self.context.i32_type();
+        let handler = self.panic_handler("sloth_index_panic", &[i32_type.into(), i32_type.into()]);
+        self.builder
+            .build_call(handler, &[index.into(), size.into()], "");
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(after_bb);
+    }
+
+    /// Declare a panic handler of the runtime, which prints an error and exits
+    /// the program.
+    fn panic_handler(
+        &self,
+        name: &str,
+        params: &[BasicMetadataTypeEnum<'ctx>],
+    ) -> FunctionValue<'ctx> {
+        if let Some(handler) = self.module.get_function(name) {
+            return handler;
+        }
+
+        let fn_type = self.context.void_type().fn_type(params, false);
+        let handler = self.module.add_function(name, fn_type, None);
+
+        // The handler exits, so the checks are kept off the hot path
+        for name in ["noreturn", "cold", "nounwind"] {
+            let kind = Attribute::get_named_enum_kind_id(name);
+            let attribute = self.context.create_enum_attribute(kind, 0);
+            handler.add_attribute(AttributeLoc::Function, attribute);
+        }
+
+        handler
+    }
+
+    /// Allocate a vector with room for exactly the given number of elements,
+    /// which are left for the caller to initialize. Returns the pointer to the
+    /// vector and to its elements.
+    fn codegen_vector(
+        &mut self,
+        element_type: BasicTypeEnum<'ctx>,
+        len: usize,
+    ) -> (PointerValue<'ctx>, PointerValue<'ctx>) {
+        let i32_type = self.context.i32_type();
+        let capacity = len as u64;
+
+        let inner_ptr = self
+            .builder
+            .build_array_malloc(
+                element_type,
+                i32_type.const_int(capacity, false),
+                "vecinnerptr",
+            )
+            .unwrap();
+
+        let vector_type = self.context.struct_type(
+            &[
+                i32_type.as_basic_type_enum(),
+                i32_type.as_basic_type_enum(),
+                inner_ptr.get_type().as_basic_type_enum(),
+            ],
+            false,
+        );
+
+        let vector_ptr = self.builder.build_malloc(vector_type, "vecptr").unwrap();
+
+        // Set the size and capacity values
+        let size_ptr = self
+            .builder
+            .build_struct_gep(vector_type, vector_ptr, 0, "size")
+            .unwrap();
+        let cap_ptr = self
+            .builder
+            .build_struct_gep(vector_type, vector_ptr, 1, "cap")
+            .unwrap();
+        let inner = self
+            .builder
+            .build_struct_gep(vector_type, vector_ptr, 2, "inner")
+            .unwrap();
+
+        self.builder
+            .build_store(size_ptr, i32_type.const_int(len as u64, false));
+        self.builder
+            .build_store(cap_ptr, i32_type.const_int(capacity, false));
+        self.builder.build_store(inner, inner_ptr);
+
+        (vector_ptr, inner_ptr)
+    }
+
+    fn codegen_value(&mut self, value: Literal) -> BasicValueEnum<'ctx> {
         match value {
             Literal::Integer(value) => self
                 .context
@@ -524,14 +1575,7 @@ impl<'ctx> Codegen<'ctx> {
                 let element_type = self.type_as_basic_type(values[0].typ.clone().unwrap());
                 let i32_type = self.context.i32_type();
 
-                let inner_ptr = self
-                    .builder
-                    .build_array_malloc(
-                        element_type,
-                        i32_type.const_int(57600, false),
-                        "vecinnerptr",
-                    )
-                    .unwrap();
+                let (vector_ptr, inner_ptr) = self.codegen_vector(element_type, values.len());
 
                 for (idx, value) in values.iter().enumerate() {
                     let value = self.codegen_expr(value).unwrap();
@@ -547,38 +1591,57 @@ impl<'ctx> Codegen<'ctx> {
                     self.builder.build_store(value_ptr, value);
                 }
 
-                let vector_type = self.context.struct_type(
-                    &[
-                        i32_type.as_basic_type_enum(),
-                        i32_type.as_basic_type_enum(),
-                        inner_ptr.get_type().as_basic_type_enum(),
-                    ],
-                    false,
-                );
+                vector_ptr.as_basic_value_enum()
+            }
+            Literal::Repeat(value, count) => {
+                let element_type = self.type_as_basic_type(value.typ.clone().unwrap());
+                let i32_type = self.context.i32_type();
 
-                let vector_ptr = self.builder.build_malloc(vector_type, "vecptr").unwrap();
+                // Analysis folded the count into a literal
+                let ExprKind::Literal(Literal::Integer(count)) = count.kind else {
+                    unreachable!("Array sizes are constant");
+                };
 
-                // Set the size and capacity values
-                let size_ptr = self
-                    .builder
-                    .build_struct_gep(vector_type, vector_ptr, 0, "size")
-                    .unwrap();
-                let cap_ptr = self
-                    .builder
-                    .build_struct_gep(vector_type, vector_ptr, 1, "cap")
-                    .unwrap();
-                let inner = self
-                    .builder
-                    .build_struct_gep(vector_type, vector_ptr, 2, "inner")
-                    .unwrap();
+                let value = self.codegen_expr(&value).unwrap();
+                let (vector_ptr, inner_ptr) = self.codegen_vector(element_type, count as usize);
 
-                self.builder
-                    .build_store(size_ptr, i32_type.const_int(values.len() as u64, false));
+                // The value is stored into every element by a loop counting up
+                // to the size
+                if count > 0 {
+                    let func = self.current_func.unwrap();
+                    let start_bb = self.builder.get_insert_block().unwrap();
+                    let loop_bb = self.context.append_basic_block(func, "repeat");
+                    let after_bb = self.context.append_basic_block(func, "repeated");
 
-                self.builder
-                    .build_store(cap_ptr, i32_type.const_int(57600, false));
+                    self.builder.build_unconditional_branch(loop_bb);
+                    self.builder.position_at_end(loop_bb);
+
+                    let phi = self.builder.build_phi(i32_type, "index");
+                    phi.add_incoming(&[(&i32_type.const_zero(), start_bb)]);
+                    let index = phi.as_basic_value().into_int_value();
+
+                    let value_ptr = unsafe {
+                        self.builder
+                            .build_gep(element_type, inner_ptr, &[index], "")
+                    };
+                    self.builder.build_store(value_ptr, value);
 
-                self.builder.build_store(inner, inner_ptr);
+                    let next =
+                        self.builder
+                            .build_int_add(index, i32_type.const_int(1, false), "next");
+                    let done = self.builder.build_int_compare(
+                        IntPredicate::EQ,
+                        next,
+                        i32_type.const_int(count as u64, false),
+                        "done",
+                    );
+                    self.builder
+                        .build_conditional_branch(done, after_bb, loop_bb);
+
+                    phi.add_incoming(&[(&next, loop_bb)]);
+
+                    self.builder.position_at_end(after_bb);
+                }
 
                 vector_ptr.as_basic_value_enum()
             }
@@ -616,7 +1679,12 @@ impl<'ctx> Codegen<'ctx> {
                 //     0,
                 //     i32_type.const_int(values.len() as u64, false),
                 // );
-                self.builder.build_store(ptr, const_arr);
+                let arr_ptr = self.builder.build_pointer_cast(
+                    ptr,
+                    const_arr.get_type().ptr_type(AddressSpace::default()),
+                    "",
+                );
+                self.builder.build_store(arr_ptr, const_arr);
 
                 ptr.as_basic_value_enum()
             }
@@ -630,6 +1698,10 @@ impl<'ctx> Codegen<'ctx> {
             Type::Integer => self.context.i32_type().into(),
             Type::Float => self.context.f32_type().into(),
             Type::Boolean => self.context.bool_type().into(),
+            Type::Pointer { typ } => self
+                .type_as_basic_type(*typ)
+                .ptr_type(AddressSpace::default())
+                .as_basic_type_enum(),
             Type::Array { typ, .. } => {
                 let i32_type = self.context.i32_type().as_basic_type_enum();
 
@@ -651,48 +1723,243 @@ impl<'ctx> Codegen<'ctx> {
                     .ptr_type(AddressSpace::default())
                     .as_basic_type_enum()
             }
+            Type::Struct { fields, .. } => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(_, typ)| self.type_as_basic_type(typ))
+                    .collect_vec();
+
+                self.context.struct_type(&fields, false).into()
+            }
+            Type::Param(name) => self.type_as_basic_type(self.type_args[&name].clone()),
+            Type::Enum { variants, .. } => {
+                // Enums are a tag followed by a payload big enough for the
+                // fields of every variant. The payload is made out of integers
+                // as wide as the strictest alignment of the variants so it is
+                // aligned for all of them.
+                let target_data = self.target_data();
+                let (size, align) = variants
+                    .iter()
+                    .map(|(_, fields)| self.variant_type(fields))
+                    .map(|it| {
+                        (
+                            target_data.get_abi_size(&it),
+                            target_data.get_abi_alignment(&it),
+                        )
+                    })
+                    .fold((0, 1), |(size, align), (variant_size, variant_align)| {
+                        (size.max(variant_size), align.max(variant_align))
+                    });
+
+                let unit = self.context.custom_width_int_type(align * 8);
+                let payload = unit.array_type(((size + align as u64 - 1) / align as u64) as u32);
+
+                self.context
+                    .struct_type(&[self.context.i32_type().into(), payload.into()], false)
+                    .into()
+            }
             _ => todo!(),
         }
     }
 
+    /// Struct of the fields of an enum variant, which is how they are stored
+    /// in the payload of the enum.
+    fn variant_type(&self, fields: &[Type]) -> StructType<'ctx> {
+        let fields = fields
+            .iter()
+            .map(|it| self.type_as_basic_type(it.clone()))
+            .collect_vec();
+
+        self.context.struct_type(&fields, false)
+    }
+
+    /// Layout information of the target, only valid once the target machine
+    /// has been created.
+    fn target_data(&self) -> TargetData {
+        let layout = self.module.get_data_layout();
+        TargetData::create(&layout.as_str().to_string_lossy())
+    }
+
     fn type_as_metadata_type(&self, typ: Type) -> BasicMetadataTypeEnum<'ctx> {
         self.type_as_basic_type(typ).into()
     }
 
-    pub fn write_obj(&self, file: &mut impl Write, filetype: FileType) {
-        Target::initialize_native(&InitializationConfig::default()).unwrap();
+    /// Create the machine for the target described by the options and set the
+    /// triple and data layout of the module to match it.
+    pub fn target_machine(&self, options: &TargetOptions) -> Result<TargetMachine, CodegenError> {
+        Target::initialize_all(&InitializationConfig::default());
+
+        let host_cpu = || TargetMachine::get_host_cpu_name().to_string();
+        let host_features = || TargetMachine::get_host_cpu_features().to_string();
+
+        let (triple, default_cpu, default_features) = match &options.triple {
+            Some(triple) => (TargetTriple::create(triple), "generic".to_owned(), None),
+            None => (
+                TargetMachine::get_default_triple(),
+                host_cpu(),
+                Some(host_features()),
+            ),
+        };
 
-        let triple = TargetMachine::get_default_triple();
-        let target = Target::from_triple(&triple).unwrap();
+        // The features of the host only apply if code is generated for its CPU
+        let (cpu, default_features) = match options.cpu.as_deref() {
+            Some("native") => (host_cpu(), Some(host_features())),
+            Some(cpu) => (cpu.to_owned(), None),
+            None => (default_cpu, default_features),
+        };
+        let features = options
+            .features
+            .clone()
+            .or(default_features)
+            .unwrap_or_default();
+
+        let target = Target::from_triple(&triple)
+            .map_err(|err| CodegenError::UnknownTarget(triple_name(&triple), err.to_string()))?;
         let machine = target
             .create_target_machine(
                 &triple,
-                "x86-64",
-                "",
-                OptimizationLevel::Aggressive,
-                RelocMode::Default,
+                &cpu,
+                &features,
+                options.opt_level,
+                options.reloc,
                 CodeModel::Default,
             )
-            .unwrap();
+            .ok_or_else(|| CodegenError::TargetMachine(triple_name(&triple)))?;
 
         self.module.set_triple(&triple);
+        self.module
+            .set_data_layout(&machine.get_target_data().get_data_layout());
+
+        Ok(machine)
+    }
+
+    /// JIT compile the module for the host and call its `main` function with
+    /// the arguments, the first argument being the name of the program.
+    /// Functions the module declares but doesn't define are resolved from the
+    /// process, so libraries providing them have to be loaded beforehand.
+    pub fn run_jit(
+        &self,
+        args: &[&str],
+        opt_level: OptimizationLevel,
+    ) -> Result<i32, CodegenError> {
+        Target::initialize_native(&InitializationConfig::default()).map_err(CodegenError::Llvm)?;
+
+        let main = self
+            .module
+            .get_function("main")
+            .ok_or(CodegenError::NoMain)?;
+
+        // LLVM aborts when main takes anything else than argc and argv, or
+        // returns anything else than an int or nothing
+        let argv_type = self
+            .context
+            .i8_type()
+            .ptr_type(AddressSpace::default())
+            .ptr_type(AddressSpace::default());
+        let params = main.get_type().get_param_types();
+        if params.len() > 2
+            || params
+                .get(0)
+                .map_or(false, |it| *it != self.context.i32_type().into())
+            || params.get(1).map_or(false, |it| *it != argv_type.into())
+            || main
+                .get_type()
+                .get_return_type()
+                .map_or(false, |it| it != self.context.i32_type().into())
+        {
+            return Err(CodegenError::MainSignature);
+        }
+
+        let engine = self
+            .module
+            .create_jit_execution_engine(opt_level)
+            .map_err(|err| CodegenError::Llvm(err.to_string()))?;
+
+        // Globals are initialized by static constructors before main runs,
+        // the same way the C runtime does it
+        engine.run_static_constructors();
+
+        // Safety: main is called the same way the C runtime would call it
+        Ok(unsafe { engine.run_function_as_main(main, args) })
+    }
+
+    /// Check that the generated module is well formed, LLVM may crash or
+    /// silently miscompile modules which aren't. Debug info is finalized
+    /// beforehand, so this has to be done before the module is written.
+    pub fn verify(&self) -> Result<(), CodegenError> {
+        if let Some(debug) = &self.debug {
+            debug.finalize();
+        }
+
+        self.module
+            .verify()
+            .map_err(|err| CodegenError::Verify(err.to_string().trim_end().to_owned()))
+    }
+
+    /// Run the LLVM optimization pipeline matching the options over the module,
+    /// the module has to be valid. Nothing is done without optimizations so
+    /// that the generated code stays close to the source when debugging.
+    pub fn optimize(
+        &self,
+        machine: &TargetMachine,
+        options: &TargetOptions,
+    ) -> Result<(), CodegenError> {
+        let pipeline = match options.opt_level {
+            _ if options.optimize_size => "default<Os>",
+            OptimizationLevel::None => return Ok(()),
+            OptimizationLevel::Less => "default<O1>",
+            OptimizationLevel::Default => "default<O2>",
+            OptimizationLevel::Aggressive => "default<O3>",
+        };
+
+        // Vectorization is off by default, it is enabled for the same levels
+        // as clang enables it
+        let vectorize = matches!(
+            options.opt_level,
+            OptimizationLevel::Default | OptimizationLevel::Aggressive
+        );
+        let pass_options = PassBuilderOptions::create();
+        pass_options.set_loop_vectorization(vectorize && !options.optimize_size);
+        pass_options.set_loop_slp_vectorization(vectorize);
+
+        self.module
+            .run_passes(pipeline, machine, pass_options)
+            .map_err(|err| CodegenError::Llvm(err.to_string()))
+    }
+
+    /// Write the module as textual LLVM IR.
+    pub fn write_ir(&self, file: &mut impl Write) -> Result<(), CodegenError> {
+        file.write_all(self.module.print_to_string().to_bytes())?;
+        Ok(())
+    }
+
+    /// Write the module as LLVM bitcode.
+    pub fn write_bitcode(&self, file: &mut impl Write) -> Result<(), CodegenError> {
+        file.write_all(self.module.write_bitcode_to_memory().as_slice())?;
+        Ok(())
+    }
 
+    /// Write the module as an object or assembly file.
+    pub fn write_obj(
+        &self,
+        machine: &TargetMachine,
+        file: &mut impl Write,
+        filetype: FileType,
+    ) -> Result<(), CodegenError> {
         let buffer = machine
             .write_to_memory_buffer(&self.module, filetype)
-            .unwrap();
+            .map_err(|err| CodegenError::Llvm(err.to_string()))?;
 
-        file.write_all(buffer.as_slice()).unwrap();
+        file.write_all(buffer.as_slice())?;
+        Ok(())
     }
 }
 
 #[allow(non_snake_case)]
 impl<'ctx> Codegen<'ctx> {
-    fn _setup(&mut self, name: &str, func_type: FunctionType<'ctx>) {
+    fn _setup(&mut self, func: FunctionValue<'ctx>) {
         // Preparing for function
-        self.references.clear();
-
-        // Making the function
-        let func = self.module.add_function(name, func_type, None);
+        self.references.clone_from(&self.globals);
 
         self.current_func = Some(func);
         self.current_func_void = true;
@@ -764,15 +2031,66 @@ impl<'ctx> Codegen<'ctx> {
         (size, cap, inner)
     }
 
-    fn INTRINSIC_vpush(&mut self, name: &str, typ: Type) {
-        // Setup function
-        let array_input = self.type_as_basic_type(Type::Array {
-            typ: Box::new(typ.clone()),
+    /// Reallocate the backing array of a vector to hold `capacity` elements and
+    /// store the new pointer and capacity back into the vector.
+    fn _resize(
+        &mut self,
+        element_type: BasicTypeEnum<'ctx>,
+        cap_ptr: PointerValue<'ctx>,
+        inner_ptr: PointerValue<'ctx>,
+        inner: PointerValue<'ctx>,
+        capacity: IntValue<'ctx>,
+    ) {
+        let i64_type = self.context.i64_type();
+        let i8_ptr_type = self.context.i8_type().ptr_type(AddressSpace::default());
+
+        let realloc = self.module.get_function("realloc").unwrap_or_else(|| {
+            let fn_type = i8_ptr_type.fn_type(&[i8_ptr_type.into(), i64_type.into()], false);
+            self.module.add_function("realloc", fn_type, None)
         });
-        let inputs = &[array_input.into(), self.type_as_metadata_type(typ.clone())];
-        let func_type = self.context.void_type().fn_type(inputs, false);
-        self._setup(&format!("vpush{name}"), func_type);
+
+        let element_size = element_type.size_of().unwrap();
+        let capacity_wide = self.builder.build_int_z_extend(capacity, i64_type, "");
+        let bytes = self
+            .builder
+            .build_int_mul(capacity_wide, element_size, "bytes");
+
+        let old = self.builder.build_pointer_cast(inner, i8_ptr_type, "");
+        let new = self
+            .builder
+            .build_call(realloc, &[old.into(), bytes.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+
+        // The program can't continue without the elements, so a failed
+        // reallocation is a panic
         let func = self.current_func.unwrap();
+        let failed_bb = self.context.append_basic_block(func, "alloc failed");
+        let allocated_bb = self.context.append_basic_block(func, "allocated");
+
+        let failed = self.builder.build_is_null(new, "failed");
+        self.builder
+            .build_conditional_branch(failed, failed_bb, allocated_bb);
+
+        self.builder.position_at_end(failed_bb);
+        let handler = self.panic_handler("sloth_alloc_panic", &[i64_type.into()]);
+        self.builder.build_call(handler, &[bytes.into()], "");
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(allocated_bb);
+        let new = self
+            .builder
+            .build_pointer_cast(new, inner.get_type(), "newinner");
+
+        self.builder.build_store(inner_ptr, new);
+        self.builder.build_store(cap_ptr, capacity);
+    }
+
+    fn INTRINSIC_vpush(&mut self, func: FunctionValue<'ctx>, typ: Type) {
+        // Setup function
+        self._setup(func);
 
         // Types
         let element_type = self.type_as_basic_type(typ);
@@ -781,9 +2099,34 @@ impl<'ctx> Codegen<'ctx> {
         // Getting the pointers and values needed
         let vector_ptr = func.get_nth_param(0).unwrap().into_pointer_value();
         let (size_ptr, cap_ptr, inner_ptr) = self._get_ptrs(element_type, vector_ptr);
-        let (size, _cap, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
+        let (size, cap, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
 
-        // FIXME: Array cant grow as of now
+        // Double the capacity when the backing array is full
+        let grow_bb = self.context.append_basic_block(func, "grow");
+        let push_bb = self.context.append_basic_block(func, "push");
+
+        let full = self
+            .builder
+            .build_int_compare(IntPredicate::UGE, size, cap, "full");
+        self.builder
+            .build_conditional_branch(full, grow_bb, push_bb);
+
+        self.builder.position_at_end(grow_bb);
+        let doubled = self
+            .builder
+            .build_int_mul(cap, i32_type.const_int(2, false), "");
+        let is_empty =
+            self.builder
+                .build_int_compare(IntPredicate::EQ, cap, i32_type.const_zero(), "");
+        let capacity = self
+            .builder
+            .build_select(is_empty, i32_type.const_int(4, false), doubled, "newcap")
+            .into_int_value();
+        self._resize(element_type, cap_ptr, inner_ptr, inner, capacity);
+        self.builder.build_unconditional_branch(push_bb);
+
+        self.builder.position_at_end(push_bb);
+        let (_, _, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
 
         // Put the new element into backing array
         let element = func.get_nth_param(1).unwrap();
@@ -800,25 +2143,9 @@ impl<'ctx> Codegen<'ctx> {
         self.builder.build_return(None);
     }
 
-    fn INTRINSIC_vpop(&mut self, name: &str, typ: Type) {
+    fn INTRINSIC_vpop(&mut self, func: FunctionValue<'ctx>, typ: Type) {
         // Setup function
-        let array_input = self.type_as_basic_type(Type::Array {
-            typ: Box::new(typ.clone()),
-        });
-        let inputs = &[array_input.into()];
-        let func_type = match typ {
-            Type::Integer => self.context.i32_type().fn_type(inputs, false),
-            Type::Float => self.context.f32_type().fn_type(inputs, false),
-            Type::Boolean => self.context.bool_type().fn_type(inputs, false),
-            Type::String => self
-                .context
-                .i8_type()
-                .ptr_type(AddressSpace::default())
-                .fn_type(inputs, false),
-            _ => panic!(),
-        };
-        self._setup(&format!("vpop{name}"), func_type);
-        let func = self.current_func.unwrap();
+        self._setup(func);
 
         // Types
         let element_type = self.type_as_basic_type(typ);
@@ -827,14 +2154,13 @@ impl<'ctx> Codegen<'ctx> {
         // Getting the pointers and values needed
         let vector_ptr = func.get_nth_param(0).unwrap().into_pointer_value();
         let (size_ptr, cap_ptr, inner_ptr) = self._get_ptrs(element_type, vector_ptr);
-        let (size, _cap, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
-
-        // FIXME: Array cant shrink as of now
+        let (size, cap, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
 
         // Get the last element in the array
         let element_idx = self
             .builder
             .build_int_sub(size, i32_type.const_int(1, false), "");
+        self.codegen_bounds_check(element_idx, size);
         let element_ptr = unsafe {
             self.builder
                 .build_gep(element_type, inner, &[element_idx], "elementptr")
@@ -846,30 +2172,43 @@ impl<'ctx> Codegen<'ctx> {
         // Decrease the size tracker
         self.builder.build_store(size_ptr, element_idx);
 
+        // Halve the capacity once the vector is a quarter full, so a push right
+        // after a pop doesn't immediately have to grow it again
+        let shrink_bb = self.context.append_basic_block(func, "shrink");
+        let return_bb = self.context.append_basic_block(func, "return");
+
+        let quarter = self
+            .builder
+            .build_int_unsigned_div(cap, i32_type.const_int(4, false), "");
+        let sparse = self
+            .builder
+            .build_int_compare(IntPredicate::ULE, element_idx, quarter, "");
+        let large = self.builder.build_int_compare(
+            IntPredicate::UGT,
+            cap,
+            i32_type.const_int(4, false),
+            "",
+        );
+        let should_shrink = self.builder.build_and(sparse, large, "shouldshrink");
+        self.builder
+            .build_conditional_branch(should_shrink, shrink_bb, return_bb);
+
+        self.builder.position_at_end(shrink_bb);
+        let capacity =
+            self.builder
+                .build_int_unsigned_div(cap, i32_type.const_int(2, false), "newcap");
+        self._resize(element_type, cap_ptr, inner_ptr, inner, capacity);
+        self.builder.build_unconditional_branch(return_bb);
+
+        self.builder.position_at_end(return_bb);
+
         // Return element
         self.builder.build_return(Some(&element));
     }
 
-    fn INTRINSIC_vget(&mut self, name: &str, typ: Type) {
+    fn INTRINSIC_vget(&mut self, func: FunctionValue<'ctx>, typ: Type) {
         // Setup function
-        let array_input = self.type_as_basic_type(Type::Array {
-            typ: Box::new(typ.clone()),
-        });
-        // [Array, Index]
-        let inputs = &[array_input.into(), self.context.i32_type().into()];
-        let func_type = match typ {
-            Type::Integer => self.context.i32_type().fn_type(inputs, false),
-            Type::Float => self.context.f32_type().fn_type(inputs, false),
-            Type::Boolean => self.context.bool_type().fn_type(inputs, false),
-            Type::String => self
-                .context
-                .i8_type()
-                .ptr_type(AddressSpace::default())
-                .fn_type(inputs, false),
-            _ => panic!(),
-        };
-        self._setup(&format!("vget{name}"), func_type);
-        let func = self.current_func.unwrap();
+        self._setup(func);
 
         // Types
         let element_type = self.type_as_basic_type(typ);
@@ -877,10 +2216,11 @@ impl<'ctx> Codegen<'ctx> {
         // Getting the pointers and values needed
         let vector_ptr = func.get_nth_param(0).unwrap().into_pointer_value();
         let (size_ptr, cap_ptr, inner_ptr) = self._get_ptrs(element_type, vector_ptr);
-        let (_, _, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
+        let (size, _, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
 
         // Get the selected element in the array
         let element_idx = func.get_nth_param(1).unwrap().into_int_value();
+        self.codegen_bounds_check(element_idx, size);
         let element_ptr = unsafe {
             self.builder
                 .build_gep(element_type, inner, &[element_idx], "elementptr")
@@ -893,20 +2233,9 @@ impl<'ctx> Codegen<'ctx> {
         self.builder.build_return(Some(&element));
     }
 
-    fn INTRINSIC_vset(&mut self, name: &str, typ: Type) {
+    fn INTRINSIC_vset(&mut self, func: FunctionValue<'ctx>, typ: Type) {
         // Setup function
-        let array_input = self.type_as_basic_type(Type::Array {
-            typ: Box::new(typ.clone()),
-        });
-        // [Array, Index, Value]
-        let inputs = &[
-            array_input.into(),
-            self.context.i32_type().into(),
-            self.type_as_metadata_type(typ.clone()),
-        ];
-        let func_type = self.context.void_type().fn_type(inputs, false);
-        self._setup(&format!("vset{name}"), func_type);
-        let func = self.current_func.unwrap();
+        self._setup(func);
 
         // Types
         let element_type = self.type_as_basic_type(typ);
@@ -914,10 +2243,11 @@ impl<'ctx> Codegen<'ctx> {
         // Getting the pointers and values needed
         let vector_ptr = func.get_nth_param(0).unwrap().into_pointer_value();
         let (size_ptr, cap_ptr, inner_ptr) = self._get_ptrs(element_type, vector_ptr);
-        let (_, _, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
+        let (size, _, inner) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
 
         // Get the selected element in the array
         let element_idx = func.get_nth_param(1).unwrap().into_int_value();
+        self.codegen_bounds_check(element_idx, size);
         let element_ptr = unsafe {
             self.builder
                 .build_gep(element_type, inner, &[element_idx], "elementptr")
@@ -928,37 +2258,12 @@ impl<'ctx> Codegen<'ctx> {
         self.builder.build_return(None);
     }
 
-    //    fn INTRINSIC_vremove(&mut self, name: &str, typ: Type) {
-    //        // Setup function
-    //        let array_input = self.type_as_basic_type(Type::Array {
-    //            typ: Box::new(typ.clone()),
-    //        });
-    //        // [Array, Index]
-    //        let inputs = &[array_input.into(), self.context.i32_type().into()];
-    //        let func_type = self.context.void_type().fn_type(inputs, false);
-    //        self._setup(&format!("vremove{name}"), func_type);
-    //        let _func = self.current_func.unwrap();
-    //
-    //        // Types
-    //        let _element_type = self.type_as_basic_type(typ);
-    //
-    //        // FIXME: Array cant shrink as of now
-    //        // TODO: vremove
-    //    }
-
-    fn INTRINSIC_vlen(&mut self) {
+    fn INTRINSIC_vlen(&mut self, func: FunctionValue<'ctx>, typ: Type) {
         // Setup function
-        let array_input = self.type_as_basic_type(Type::Array {
-            typ: Box::new(Type::Integer),
-        });
-        // [Array, Index]
-        let inputs = &[array_input.into(), self.context.i32_type().into()];
-        let func_type = self.context.i32_type().fn_type(inputs, false);
-        self._setup("vlen", func_type);
-        let func = self.current_func.unwrap();
+        self._setup(func);
 
         // Getting the pointers and values needed
-        let element_type = self.type_as_basic_type(Type::Integer); // Dummy - Not actually used
+        let element_type = self.type_as_basic_type(typ);
         let vector_ptr = func.get_nth_param(0).unwrap().into_pointer_value();
         let (size_ptr, cap_ptr, inner_ptr) = self._get_ptrs(element_type, vector_ptr);
         let (size, _, _) = self._get_values(element_type, size_ptr, cap_ptr, inner_ptr);
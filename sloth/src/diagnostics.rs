@@ -0,0 +1,440 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::lexer::Span;
+use crate::modules::SourceFile;
+
+/// How diagnostics are written to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Default, clap::ValueEnum)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    /// One JSON object per line, meant to be consumed by other tools.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// A span of source code the diagnostic points at. Primary labels mark the
+/// cause of the diagnostic, secondary labels add context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn emit(&self, files: &[SourceFile], format: MessageFormat) {
+        match format {
+            MessageFormat::Human => eprintln!("{}", self.render(files)),
+            MessageFormat::Json => eprintln!("{}", self.to_json(files)),
+        }
+    }
+
+    /// Render the diagnostic for humans, showing the source lines the labels
+    /// point at with the labelled spans underlined.
+    ///
+    /// ```text
+    /// error: Mismatched types
+    ///  --> examples/hello.sloth:2:22
+    ///   |
+    /// 2 |     var x: Int = 5 + 2.0;
+    ///   |                      ^^^ expected Int, found Float
+    /// ```
+    pub fn render(&self, files: &[SourceFile]) -> String {
+        let mut out = String::new();
+        writeln!(out, "{}: {}", self.severity.name(), self.message).unwrap();
+
+        // Labels are grouped by the line they start on, every group is rendered
+        // as a single source line with all of its labels underneath. Lines are
+        // shown in source order, starting with the file of the primary label.
+        let primary_file = self
+            .labels
+            .iter()
+            .find(|it| it.primary)
+            .map(|it| it.span.file);
+        let lines = self
+            .labels
+            .iter()
+            .sorted_by_key(|it| {
+                (
+                    Some(it.span.file) != primary_file,
+                    it.span.file,
+                    it.span.start.row,
+                )
+            })
+            .map(|it| (it.span.file, it.span.start.row))
+            .unique()
+            .collect_vec();
+
+        let gutter = lines
+            .iter()
+            .map(|(_, row)| (row + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+        let pad = " ".repeat(gutter);
+
+        let mut current_file = None;
+        for &(file, row) in &lines {
+            let labels = self
+                .labels
+                .iter()
+                .filter(|it| it.span.file == file && it.span.start.row == row)
+                .sorted_by_key(|it| it.span.start.col)
+                .collect_vec();
+
+            if current_file != Some(file) {
+                let arrow = if current_file.is_none() { "-->" } else { ":::" };
+                let label = self
+                    .labels
+                    .iter()
+                    .filter(|it| it.span.file == file)
+                    .find(|it| it.primary)
+                    .unwrap_or(labels[0]);
+                let start = label.span.start;
+                writeln!(
+                    out,
+                    "{pad}{arrow} {}:{}:{}",
+                    files[file].path.display(),
+                    start.row + 1,
+                    start.col + 1
+                )
+                .unwrap();
+                writeln!(out, "{pad} |").unwrap();
+                current_file = Some(file);
+            }
+
+            let source = files[file].source.lines().nth(row as usize).unwrap_or("");
+            writeln!(
+                out,
+                "{:>gutter$} | {}",
+                row + 1,
+                source.replace('\t', "    ")
+            )
+            .unwrap();
+
+            for line in render_labels(source, &labels) {
+                writeln!(out, "{pad} | {}", line.trim_end()).unwrap();
+            }
+        }
+
+        for note in &self.notes {
+            writeln!(out, "{pad} = note: {note}").unwrap();
+        }
+
+        out
+    }
+
+    /// Render the diagnostic as a single line of JSON.
+    pub fn to_json(&self, files: &[SourceFile]) -> String {
+        #[derive(Serialize)]
+        struct JsonPosition {
+            line: u32,
+            column: u32,
+            offset: usize,
+        }
+
+        #[derive(Serialize)]
+        struct JsonLabel<'a> {
+            file: String,
+            start: JsonPosition,
+            end: JsonPosition,
+            message: &'a str,
+            primary: bool,
+        }
+
+        #[derive(Serialize)]
+        struct JsonDiagnostic<'a> {
+            severity: Severity,
+            message: &'a str,
+            labels: Vec<JsonLabel<'a>>,
+            notes: &'a [String],
+        }
+
+        let labels = self
+            .labels
+            .iter()
+            .map(|it| JsonLabel {
+                file: files[it.span.file].path.display().to_string(),
+                start: JsonPosition {
+                    line: it.span.start.row + 1,
+                    column: it.span.start.col + 1,
+                    offset: it.span.start.index,
+                },
+                end: JsonPosition {
+                    line: it.span.end.row + 1,
+                    column: it.span.end.col + 1,
+                    offset: it.span.end.index,
+                },
+                message: &it.message,
+                primary: it.primary,
+            })
+            .collect_vec();
+
+        let diagnostic = JsonDiagnostic {
+            severity: self.severity,
+            message: &self.message,
+            labels,
+            notes: &self.notes,
+        };
+
+        serde_json::to_string(&diagnostic).unwrap()
+    }
+}
+
+/// Collects the diagnostics of every stage of compilation so they can be
+/// reported together instead of stopping at the first error.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, diagnostic: impl Into<Diagnostic>) {
+        self.diagnostics.push(diagnostic.into());
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|it| it.severity == Severity::Error)
+    }
+
+    pub fn emit(&self, files: &[SourceFile], format: MessageFormat) {
+        for diagnostic in &self.diagnostics {
+            diagnostic.emit(files, format);
+        }
+    }
+}
+
+impl<T: Into<Diagnostic>> Extend<T> for Diagnostics {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.diagnostics.extend(iter.into_iter().map(Into::into));
+    }
+}
+
+/// Build the lines underneath a source line, labels must be sorted by column.
+/// Tabs in the source line are expected to be rendered as four spaces.
+/// The rightmost label gets its message next to the underline and the others
+/// are connected to their messages on the following lines.
+fn render_labels(source: &str, labels: &[&Label]) -> Vec<String> {
+    // Columns are converted to display columns as tabs were expanded
+    let column = |col: u32| -> usize {
+        source
+            .chars()
+            .take(col as usize)
+            .map(|it| if it == '\t' { 4 } else { 1 })
+            .sum()
+    };
+
+    let mut underline = String::new();
+    for label in labels {
+        let start = column(label.span.start.col);
+        let end = if label.span.end.row == label.span.start.row {
+            column(label.span.end.col)
+        } else {
+            column(source.chars().count() as u32)
+        };
+
+        let marker = if label.primary { '^' } else { '-' };
+        let width = end.saturating_sub(start).max(1);
+
+        if underline.len() < start {
+            underline.push_str(&" ".repeat(start - underline.len()));
+        }
+        underline.truncate(start);
+        underline.extend(std::iter::repeat(marker).take(width));
+    }
+
+    let (last, rest) = labels.split_last().unwrap();
+    if !last.message.is_empty() {
+        write!(underline, " {}", last.message).unwrap();
+    }
+
+    let mut lines = vec![underline];
+
+    let hanging = rest
+        .iter()
+        .filter(|it| !it.message.is_empty())
+        .map(|it| (column(it.span.start.col), &it.message))
+        .collect_vec();
+
+    for index in (0..hanging.len()).rev() {
+        let mut connector = String::new();
+        let mut message = String::new();
+
+        for (position, (col, _)) in hanging[..=index].iter().enumerate() {
+            connector.push_str(&" ".repeat(col.saturating_sub(connector.len())));
+            connector.push('|');
+
+            if position < index {
+                message.push_str(&" ".repeat(col.saturating_sub(message.len())));
+                message.push('|');
+            }
+        }
+
+        let (col, text) = hanging[index];
+        message.push_str(&" ".repeat(col.saturating_sub(message.len())));
+        message.push_str(text);
+
+        lines.push(connector);
+        lines.push(message);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::Diagnostic;
+    use crate::lexer::{Location, Span};
+    use crate::modules::SourceFile;
+
+    fn span(row: u32, start: u32, end: u32) -> Span {
+        Span {
+            file: 0,
+            start: Location {
+                index: 0,
+                row,
+                col: start,
+            },
+            end: Location {
+                index: 0,
+                row,
+                col: end,
+            },
+        }
+    }
+
+    #[test]
+    fn render_multiple_labels() {
+        let files = [SourceFile {
+            path: PathBuf::from("main.sloth"),
+            source: "fn main() Int {\n    var x: Int = 5 + 2.0;\n}\n".to_owned(),
+        }];
+
+        let diagnostic = Diagnostic::error("Mismatched types")
+            .with_primary(span(1, 21, 24), "expected Int, found Float")
+            .with_secondary(span(1, 17, 18), "Int")
+            .with_note("both sides of '+' must have the same type");
+
+        let expected = "\
+error: Mismatched types
+ --> main.sloth:2:22
+  |
+2 |     var x: Int = 5 + 2.0;
+  |                  -   ^^^ expected Int, found Float
+  |                  |
+  |                  Int
+  = note: both sides of '+' must have the same type
+";
+
+        let generated = diagnostic.render(&files);
+
+        println!("Expected:\n{expected}\n\n");
+        println!("Generated:\n{generated}\n\n");
+
+        assert_eq!(expected, generated);
+    }
+
+    #[test]
+    fn render_labels_in_source_order() {
+        let files = [SourceFile {
+            path: PathBuf::from("main.sloth"),
+            source: "fn main() Int {\n    val x = 5;\n    x = 6;\n}\n".to_owned(),
+        }];
+
+        let diagnostic = Diagnostic::error("Cannot assign twice to immutable value 'x'")
+            .with_primary(span(2, 4, 9), "cannot assign twice")
+            .with_secondary(span(1, 8, 9), "first assigned here");
+
+        let expected = "\
+error: Cannot assign twice to immutable value 'x'
+ --> main.sloth:3:5
+  |
+2 |     val x = 5;
+  |         - first assigned here
+3 |     x = 6;
+  |     ^^^^^ cannot assign twice
+";
+
+        let generated = diagnostic.render(&files);
+
+        println!("Expected:\n{expected}\n\n");
+        println!("Generated:\n{generated}\n\n");
+
+        assert_eq!(expected, generated);
+    }
+
+    #[test]
+    fn render_json() {
+        let files = [SourceFile {
+            path: PathBuf::from("main.sloth"),
+            source: "foo;\n".to_owned(),
+        }];
+
+        let diagnostic =
+            Diagnostic::error("Unknown identifier 'foo'").with_primary(span(0, 0, 3), "");
+
+        let expected = r#"{"severity":"error","message":"Unknown identifier 'foo'","labels":[{"file":"main.sloth","start":{"line":1,"column":1,"offset":0},"end":{"line":1,"column":4,"offset":0},"message":"","primary":true}],"notes":[]}"#;
+
+        assert_eq!(expected, diagnostic.to_json(&files));
+    }
+}
@@ -0,0 +1,173 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs, io};
+
+/// Sloth sources of the standard library, they are loaded before the source
+/// files of every program.
+const STD_SOURCES: &[&str] = &["extern.sloth", "stdmath.sloth", "stdio.sloth"];
+
+/// C sources of the runtime the foreign functions of the standard library are
+/// implemented in.
+const RUNTIME_SOURCES: &[&str] = &["stdio.c", "stdlib.c", "stdmath.c", "stdsocket.c"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum DriverError {
+    #[error("Could not find the standard library, set SLOTH_STD to its directory")]
+    StdNotFound,
+    #[error("Could not run '{}': {1}", .0.to_string_lossy())]
+    Spawn(OsString, io::Error),
+    #[error("'{}' failed while {1}", .0.to_string_lossy())]
+    Failed(OsString, &'static str),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+/// Standard library directory containing both the sloth sources and the C
+/// runtime.
+#[derive(Debug, Clone)]
+pub struct StdLib {
+    dir: PathBuf,
+}
+
+impl StdLib {
+    /// Find the standard library. The `SLOTH_STD` environment variable takes
+    /// precedence, otherwise the directories next to the compiler executable
+    /// and the source tree the compiler was built from are searched.
+    pub fn locate() -> Result<Self, DriverError> {
+        if let Some(dir) = env::var_os("SLOTH_STD") {
+            return Ok(Self { dir: dir.into() });
+        }
+
+        let mut candidates = Vec::new();
+        if let Some(exe_dir) = env::current_exe()
+            .ok()
+            .and_then(|it| Some(it.parent()?.to_owned()))
+        {
+            candidates.push(exe_dir.join("std"));
+            candidates.push(exe_dir.join("../lib/sloth/std"));
+        }
+        candidates.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("../std"));
+
+        candidates
+            .into_iter()
+            .find(|it| it.join(STD_SOURCES[0]).is_file())
+            .map(|dir| Self { dir })
+            .ok_or(DriverError::StdNotFound)
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        STD_SOURCES.iter().map(|it| self.dir.join(it))
+    }
+
+    pub fn runtime(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        RUNTIME_SOURCES.iter().map(|it| self.dir.join(it))
+    }
+}
+
+/// Links objects into executables using the system C compiler, which can be
+/// overridden using the `CC` environment variable.
+pub struct Linker {
+    compiler: OsString,
+    /// Directory for intermediate files, removed when the linker is dropped.
+    work_dir: PathBuf,
+}
+
+impl Linker {
+    pub fn new() -> Result<Self, DriverError> {
+        let work_dir = env::temp_dir().join(format!("sloth-{}", std::process::id()));
+        fs::create_dir_all(&work_dir)?;
+
+        Ok(Self {
+            compiler: env::var_os("CC").unwrap_or_else(|| "cc".into()),
+            work_dir,
+        })
+    }
+
+    /// Path for an intermediate file which is cleaned up with the linker.
+    pub fn work_file(&self, name: &str) -> PathBuf {
+        self.work_dir.join(name)
+    }
+
+    /// Link objects, C sources and libraries into an executable. The runtime of
+    /// the standard library is compiled and linked in as well when given.
+    pub fn link<'a>(
+        &self,
+        inputs: impl IntoIterator<Item = &'a Path>,
+        std: Option<&StdLib>,
+        output: &Path,
+    ) -> Result<(), DriverError> {
+        self.link_with(&[], inputs, std, output)
+    }
+
+    /// Link objects, C sources and libraries into a shared library which can be
+    /// loaded into the compiler, used to provide the runtime to JIT compiled
+    /// programs. Objects given as input have to be position independent.
+    pub fn link_shared<'a>(
+        &self,
+        inputs: impl IntoIterator<Item = &'a Path>,
+        std: Option<&StdLib>,
+        output: &Path,
+    ) -> Result<(), DriverError> {
+        self.link_with(&["-shared", "-fPIC"], inputs, std, output)
+    }
+
+    fn link_with<'a>(
+        &self,
+        flags: &[&str],
+        inputs: impl IntoIterator<Item = &'a Path>,
+        std: Option<&StdLib>,
+        output: &Path,
+    ) -> Result<(), DriverError> {
+        let mut command = Command::new(&self.compiler);
+        command.args(flags).args(inputs);
+
+        if let Some(std) = std {
+            command.args(self.compile_runtime(std)?);
+        }
+
+        command.arg("-lm").arg("-o").arg(output);
+        self.execute(command, "linking")
+    }
+
+    /// Compile the runtime into position independent objects so they can be
+    /// linked into shared libraries as well. Warnings are silenced as they are
+    /// of no use to the user.
+    fn compile_runtime(&self, std: &StdLib) -> Result<Vec<PathBuf>, DriverError> {
+        let mut objects = Vec::new();
+        for source in std.runtime() {
+            let name = source.file_stem().unwrap().to_string_lossy();
+            let object = self.work_file(&format!("{name}.o"));
+
+            let mut command = Command::new(&self.compiler);
+            command
+                .args(["-c", "-w", "-O2", "-fPIC"])
+                .arg(&source)
+                .arg("-o")
+                .arg(&object);
+            self.execute(command, "compiling the runtime")?;
+
+            objects.push(object);
+        }
+
+        Ok(objects)
+    }
+
+    fn execute(&self, mut command: Command, action: &'static str) -> Result<(), DriverError> {
+        let status = command
+            .status()
+            .map_err(|err| DriverError::Spawn(self.compiler.clone(), err))?;
+
+        if !status.success() {
+            return Err(DriverError::Failed(self.compiler.clone(), action));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Linker {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.work_dir);
+    }
+}
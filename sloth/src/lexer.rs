@@ -7,6 +7,8 @@ use std::str::Chars;
 
 use thiserror::Error;
 
+use crate::modules::FileId;
+
 #[derive(Debug, Clone, PartialEq, Error)]
 pub enum LexerError {
     #[error("Unexpected token")]
@@ -92,8 +94,12 @@ pub enum TokenType {
     Fn,
     Return,
 
+    Struct,
+    Enum,
+
     If,
     Else,
+    Match,
 
     While,
     For,
@@ -106,6 +112,7 @@ pub enum TokenType {
     As,
 
     Foreign,
+    Use,
 
     // Other
     Literal(Literal),
@@ -113,6 +120,8 @@ pub enum TokenType {
 
     // Utility
     Error(LexerError),
+    /// End of the input, never produced by the lexer itself.
+    Eof,
 }
 
 impl Display for TokenType {
@@ -177,8 +186,11 @@ impl Display for TokenType {
             TokenType::Var => "var",
             TokenType::Fn => "fn",
             TokenType::Return => "return",
+            TokenType::Struct => "struct",
+            TokenType::Enum => "enum",
             TokenType::If => "if",
             TokenType::Else => "else",
+            TokenType::Match => "match",
             TokenType::While => "while",
             TokenType::For => "for",
             TokenType::In => "in",
@@ -187,9 +199,11 @@ impl Display for TokenType {
             TokenType::Continue => "continue",
             TokenType::As => "as",
             TokenType::Foreign => "foreign",
+            TokenType::Use => "use",
             TokenType::Literal(_) => "literal",
             TokenType::Identifier(_) => "identifier",
             TokenType::Error(_) => "error",
+            TokenType::Eof => "end of file",
         };
 
         write!(f, "{s}")
@@ -211,9 +225,10 @@ impl From<Literal> for TokenType {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Location {
-    index: usize,
+    /// Byte offset into the source file
+    pub index: usize,
     pub row: u32,
     pub col: u32,
 }
@@ -230,6 +245,44 @@ impl Location {
     }
 }
 
+/// Region of a source file, from the start location up to but not including
+/// the end location.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    /// Create a span covering both this span and the other one.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            file: self.file,
+            start: self.start,
+            end: other.end,
+        }
+    }
+
+    /// Zero sized span located at the start of this span.
+    pub fn shrink_to_start(self) -> Span {
+        Span {
+            file: self.file,
+            start: self.start,
+            end: self.start,
+        }
+    }
+
+    /// Zero sized span located at the end of this span.
+    pub fn shrink_to_end(self) -> Span {
+        Span {
+            file: self.file,
+            start: self.end,
+            end: self.end,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Token<'a> {
     pub(crate) tt: TokenType,
@@ -237,6 +290,7 @@ pub struct Token<'a> {
 
     pub start: Location,
     pub end: Location,
+    pub file: FileId,
 }
 
 pub struct Lexer<'a> {
@@ -246,11 +300,22 @@ pub struct Lexer<'a> {
 
     start: Location,
     current: Location,
+    file: FileId,
 
     // Keep track if the lexer has encountered an error to stop lexing asap
     errored: bool,
 }
 
+impl<'a> Token<'a> {
+    pub fn span(&self) -> Span {
+        Span {
+            file: self.file,
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
 impl<'a> Lexer<'a> {
     pub(crate) fn new(source: &'a str) -> Self {
         let mut chars = source.chars();
@@ -266,9 +331,16 @@ impl<'a> Lexer<'a> {
             chars,
             start: Default::default(),
             current: Default::default(),
+            file: 0,
             errored: false,
         }
     }
+
+    /// Tag every token produced by this lexer with the given source file.
+    pub(crate) fn with_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        self
+    }
 }
 
 impl<'a> Lexer<'a> {
@@ -489,8 +561,11 @@ impl<'a> Iterator for Lexer<'a> {
                     "var" => TokenType::Var,
                     "fn" => TokenType::Fn,
                     "return" => TokenType::Return,
+                    "struct" => TokenType::Struct,
+                    "enum" => TokenType::Enum,
                     "if" => TokenType::If,
                     "else" => TokenType::Else,
+                    "match" => TokenType::Match,
                     "while" => TokenType::While,
                     "for" => TokenType::For,
                     "in" => TokenType::In,
@@ -499,6 +574,7 @@ impl<'a> Iterator for Lexer<'a> {
                     "continue" => TokenType::Continue,
                     "as" => TokenType::As,
                     "foreign" => TokenType::Foreign,
+                    "use" => TokenType::Use,
                     "true" => Literal::Boolean(true).into(),
                     "false" => Literal::Boolean(false).into(),
                     _ => TokenType::Identifier(value),
@@ -522,6 +598,7 @@ impl<'a> Iterator for Lexer<'a> {
             lexeme,
             start: self.start,
             end: self.current,
+            file: self.file,
         };
 
         Some(token)
@@ -593,15 +670,19 @@ mod tests {
 
     #[test]
     fn lex_keywords() {
-        let source = "val var fn if else while for in loop break continue as true false";
+        let source =
+            "val var fn struct enum if else match while for in loop break continue as true false";
         let tokens = Lexer::new(source).map(|it| it.tt).collect_vec();
 
         assert_eq!(&tokens, &[
             TokenType::Val,
             TokenType::Var,
             TokenType::Fn,
+            TokenType::Struct,
+            TokenType::Enum,
             TokenType::If,
             TokenType::Else,
+            TokenType::Match,
             TokenType::While,
             TokenType::For,
             TokenType::In,
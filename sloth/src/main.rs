@@ -7,89 +7,428 @@
 )]
 
 pub mod analysis;
+pub mod cli;
+pub mod diagnostics;
+pub mod driver;
 pub mod lexer;
+pub mod modules;
 pub mod parser;
 pub mod symtable;
 
 #[cfg(feature = "llvm")]
 pub mod codegen;
 
-use std::{env, fs};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, Write};
+#[cfg(feature = "llvm")]
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::{env, process};
 
+use clap::Parser;
+#[cfg(feature = "llvm")]
+use cli::OptLevel;
+use cli::{BuildArgs, Cli, CodegenArgs, Command, Emit, InputArgs, RunArgs};
+use diagnostics::{Diagnostic, Diagnostics, MessageFormat};
+#[cfg(feature = "llvm")]
+use driver::Linker;
+use driver::StdLib;
 use itertools::Itertools;
 use lexer::Lexer;
-use parser::AstParser;
+use modules::{Module, ModuleLoader, SourceFile};
+use parser::graph::GraphBuilder;
 use symtable::{Symbol, SymbolTable, ValueSymbol};
 
 use crate::analysis::analyze;
 use crate::symtable::Type;
 
 fn main() {
-    let args = env::args().collect_vec();
-
-    if args.len() < 2 {
-        println!("Sloth programming language interpreter\n");
-        println!("Usage: sloth <file...>");
-        std::process::exit(1);
-    }
-
-    // Reading source files
-    let mut source = String::new();
-    for path in args.iter().skip(1) {
-        let Ok(contents) = fs::read_to_string(path) else {
-            eprintln!("Error while reading '{path}'");
-            std::process::exit(1);
-        };
-        source.push_str(&contents);
-        let len = contents.lines().collect_vec().len();
-        let padding = 1000 - len;
-        for _ in 0..padding {
-            source.push('\n');
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Build(args) => build(&args),
+        Command::Check(args) => {
+            load(&args);
         }
+        Command::Run(args) => run(&args),
     }
+}
 
-    // Parsing
-    let tokens = Lexer::new(&source).collect_vec();
-    println!("{tokens:#?}");
-    let global_symtable = mk_symtable();
+fn build(args: &BuildArgs) {
+    let format = args.input.message_format;
 
-    let mut ast = match AstParser::parse(tokens, global_symtable) {
-        Ok(node) => node,
-        Err(error) => {
-            eprintln!(
-                "Error in file {} on line {}: {error}",
-                args[1 + (error.line() / 1_000) as usize],
-                error.line() % 1000 + 1,
+    let output = args
+        .output
+        .clone()
+        .or_else(|| default_output(&args.input, args.emit));
+    let open_output = || -> Box<dyn Write> {
+        match &output {
+            Some(path) => Box::new(File::create(path).unwrap_or_else(|err| {
+                fail(
+                    format,
+                    format!("Could not create '{}': {err}", path.display()),
+                )
+            })),
+            None => Box::new(io::stdout()),
+        }
+    };
+
+    // Tokens are emitted before parsing so they are available for files that
+    // don't parse
+    if args.emit == Emit::Tokens {
+        let mut writer = open_output();
+        for path in args.input.sources() {
+            let source = fs::read_to_string(path).unwrap_or_else(|err| {
+                fail(
+                    format,
+                    format!("Could not read '{}': {err}", path.display()),
+                )
+            });
+            let tokens = Lexer::new(&source).collect_vec();
+            writeln!(writer, "{tokens:#?}").unwrap_or_else(|err| fail(format, err));
+        }
+        return;
+    }
+
+    let (files, modules) = load(&args.input);
+
+    match args.emit {
+        Emit::Tokens => unreachable!(),
+        Emit::Ast => {
+            let mut writer = open_output();
+            for module in &modules {
+                writeln!(writer, "{:#?}", module.ast).unwrap_or_else(|err| fail(format, err));
+            }
+        }
+        Emit::Dot => {
+            let mut writer = open_output();
+            for module in &modules {
+                let source = &files[module.file].source;
+                let graph = GraphBuilder::generate(Some(source), &module.ast).unwrap();
+                writeln!(writer, "{graph}").unwrap_or_else(|err| fail(format, err));
+            }
+        }
+        Emit::LlvmIr | Emit::LlvmBc | Emit::Asm | Emit::Obj | Emit::Exe => {
+            let output = output.unwrap_or_else(|| fail(format, "No output path given"));
+            compile(
+                &args.input,
+                &args.codegen,
+                &files,
+                &modules,
+                args.emit,
+                &output,
             );
-            std::process::exit(1);
+        }
+    }
+}
+
+fn run(args: &RunArgs) {
+    let format = args.input.message_format;
+    let (files, modules) = load(&args.input);
+
+    if args.jit {
+        jit(args, &files, &modules);
+    }
+
+    let executable = env::temp_dir().join(format!("sloth-run-{}", process::id()));
+    compile(
+        &args.input,
+        &args.codegen,
+        &files,
+        &modules,
+        Emit::Exe,
+        &executable,
+    );
+
+    let status = process::Command::new(&executable).args(&args.args).status();
+    let _ = fs::remove_file(&executable);
+
+    match status {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(err) => fail(format, format!("Could not run the program: {err}")),
+    }
+}
+
+/// Load, parse and analyze every source file of a program. Errors are reported
+/// and exit the process.
+fn load(input: &InputArgs) -> (Vec<SourceFile>, Vec<Module>) {
+    let format = input.message_format;
+
+    if input.sources().next().is_none() {
+        fail(format, "No sloth source files given");
+    }
+
+    // Loading and parsing source files, the standard library comes first so
+    // every file can use it
+    let std = std_lib(input);
+    let std_sources = std.iter().flat_map(StdLib::sources).collect_vec();
+
+    let mut diagnostics = Diagnostics::default();
+    let mut loader = ModuleLoader::new(mk_symtable());
+    for path in &std_sources {
+        if let Err(error) = loader.load_prelude(path) {
+            diagnostics.extend(error.diagnostics());
+        }
+    }
+    for path in input.sources() {
+        if let Err(error) = loader.load_root(path) {
+            diagnostics.extend(error.diagnostics());
+        }
+    }
+
+    for error in loader.duplicate_definitions() {
+        diagnostics.extend(error.diagnostics());
+    }
+
+    if diagnostics.has_errors() {
+        report_errors(loader.files(), format, diagnostics);
+    }
+
+    let (files, mut modules) = match loader.finish() {
+        Ok(program) => program,
+        Err(error) => {
+            diagnostics.extend(error.diagnostics());
+            report_errors(&[], format, diagnostics);
         }
     };
 
-    if let Err(error) = analyze(&mut ast) {
-        eprintln!(
-            "Error in file {} on line {}: {error}",
-            args[1 + (error.line() / 1_000) as usize],
-            error.line() % 1000 + 1,
-        );
-        std::process::exit(1);
+    // Modules are analyzed after the modules they import
+    for module in &mut modules {
+        if let Err(errors) = analyze(&mut module.ast) {
+            diagnostics.extend(&errors);
+        }
     }
 
-    // Generating code for module if LLVM enabled
-    #[cfg(feature = "llvm")]
-    {
-        use std::fs::File;
+    if diagnostics.has_errors() {
+        report_errors(&files, format, diagnostics);
+    }
+
+    (files, modules)
+}
 
-        use codegen::Codegen;
-        use inkwell::context::Context;
-        use inkwell::targets::FileType;
+/// Generate code for the modules and write it to the output, executables are
+/// linked with the C sources and objects given as input.
+#[cfg(feature = "llvm")]
+fn compile(
+    input: &InputArgs,
+    args: &CodegenArgs,
+    files: &[SourceFile],
+    modules: &[Module],
+    emit: Emit,
+    output: &Path,
+) {
+    use inkwell::context::Context;
+    use inkwell::targets::FileType;
 
-        let context = Context::create();
-        let mut codegen = Codegen::new(&context, "s");
-        let mut output_file = File::create("output.o").unwrap();
+    let format = input.message_format;
+
+    let context = Context::create();
+    let options = target_options(args);
+    let (codegen, machine) = generate(&context, format, args, &options, files, modules);
+
+    let create = |path: &Path| {
+        File::create(path).unwrap_or_else(|err| {
+            fail(
+                format,
+                format!("Could not create '{}': {err}", path.display()),
+            )
+        })
+    };
 
-        codegen.codegen(&ast);
-        codegen.write_obj(&mut output_file, FileType::Object);
+    // Invalid IR is still written out so that it can be inspected, but it isn't
+    // optimized as the passes expect valid IR
+    let verified = codegen.verify();
+    match &verified {
+        Ok(()) => codegen
+            .optimize(&machine, &options)
+            .unwrap_or_else(|err| fail(format, err)),
+        Err(err) if emit != Emit::LlvmIr => fail(format, err),
+        Err(_) => (),
     }
+
+    let result = match emit {
+        Emit::LlvmIr => codegen.write_ir(&mut create(output)).and(verified),
+        Emit::LlvmBc => codegen.write_bitcode(&mut create(output)),
+        Emit::Asm => codegen.write_obj(&machine, &mut create(output), FileType::Assembly),
+        Emit::Obj => codegen.write_obj(&machine, &mut create(output), FileType::Object),
+        Emit::Exe => {
+            let std = std_lib(input);
+            let linker = Linker::new().unwrap_or_else(|err| fail(format, err));
+            let object = linker.work_file("program.o");
+            let inputs =
+                iter::once(object.as_path()).chain(input.linker_inputs().map(PathBuf::as_path));
+
+            // Errors are reported after the linker is dropped so that its
+            // intermediate files are cleaned up
+            let result = File::create(&object)
+                .map_err(|err| err.to_string())
+                .and_then(|mut file| {
+                    codegen
+                        .write_obj(&machine, &mut file, FileType::Object)
+                        .map_err(|err| err.to_string())
+                })
+                .and_then(|_| {
+                    linker
+                        .link(inputs, std.as_ref(), output)
+                        .map_err(|err| err.to_string())
+                });
+
+            drop(linker);
+            result.unwrap_or_else(|err| fail(format, err));
+            Ok(())
+        }
+        Emit::Tokens | Emit::Ast | Emit::Dot => unreachable!(),
+    };
+
+    result.unwrap_or_else(|err| fail(format, err));
+}
+
+#[cfg(feature = "llvm")]
+fn target_options(args: &CodegenArgs) -> codegen::TargetOptions {
+    use inkwell::targets::RelocMode;
+
+    codegen::TargetOptions {
+        triple: args.target.clone(),
+        cpu: args.cpu.clone(),
+        features: args.features.clone(),
+        // The C compiler links position independent executables by default
+        reloc: args.reloc.map_or(RelocMode::PIC, Into::into),
+        opt_level: args.opt_level.into(),
+        optimize_size: args.opt_level == OptLevel::Size,
+    }
+}
+
+/// Generate code for every module. The target machine is created first so
+/// that the data layout and debug info of the module match the target.
+#[cfg(feature = "llvm")]
+fn generate<'ctx>(
+    context: &'ctx inkwell::context::Context,
+    format: MessageFormat,
+    args: &CodegenArgs,
+    options: &codegen::TargetOptions,
+    files: &[SourceFile],
+    modules: &[Module],
+) -> (codegen::Codegen<'ctx>, inkwell::targets::TargetMachine) {
+    let mut codegen = codegen::Codegen::new(context, "s");
+    let machine = codegen
+        .target_machine(options)
+        .unwrap_or_else(|err| fail(format, err));
+
+    if args.debug_info {
+        codegen.enable_debug_info(files, args.opt_level != OptLevel::None);
+    }
+    if args.unchecked {
+        codegen.disable_bounds_checks();
+    }
+
+    for module in modules {
+        codegen.codegen(&module.ast);
+    }
+
+    (codegen, machine)
+}
+
+/// JIT compile the modules and run the program inside of the compiler, the
+/// runtime and the C sources and objects given as input are loaded as a shared
+/// library. Exits with the exit code of the program.
+#[cfg(feature = "llvm")]
+fn jit(args: &RunArgs, files: &[SourceFile], modules: &[Module]) -> ! {
+    use inkwell::context::Context;
+    use inkwell::support::load_library_permanently;
+
+    let format = args.input.message_format;
+
+    // The module is optimized for the host before it is compiled
+    let context = Context::create();
+    let options = target_options(&args.codegen);
+    let (codegen, machine) = generate(&context, format, &args.codegen, &options, files, modules);
+    codegen
+        .verify()
+        .and_then(|_| codegen.optimize(&machine, &options))
+        .unwrap_or_else(|err| fail(format, err));
+
+    let std = std_lib(&args.input);
+    let linker = Linker::new().unwrap_or_else(|err| fail(format, err));
+
+    // Errors are reported after the linker is dropped so that its
+    // intermediate files are cleaned up
+    let mut result = Ok(());
+    if std.is_some() || args.input.linker_inputs().next().is_some() {
+        let library = linker.work_file("libruntime.so");
+        let inputs = args.input.linker_inputs().map(PathBuf::as_path);
+
+        result = linker
+            .link_shared(inputs, std.as_ref(), &library)
+            .map_err(|err| err.to_string())
+            .and_then(|_| {
+                // Returns true if the library could not be loaded
+                match load_library_permanently(&library.to_string_lossy()) {
+                    true => Err(format!("Could not load '{}'", library.display())),
+                    false => Ok(()),
+                }
+            });
+    }
+
+    let program = args.input.sources().last().unwrap().to_string_lossy();
+    let argv = iter::once(program.as_ref())
+        .chain(args.args.iter().map(String::as_str))
+        .collect_vec();
+
+    let result = result.and_then(|_| {
+        codegen
+            .run_jit(&argv, args.codegen.opt_level.into())
+            .map_err(|err| err.to_string())
+    });
+
+    drop(linker);
+    match result {
+        Ok(code) => process::exit(code),
+        Err(err) => fail(format, err),
+    }
+}
+
+#[cfg(not(feature = "llvm"))]
+fn jit(args: &RunArgs, _: &[SourceFile], _: &[Module]) -> ! {
+    fail(
+        args.input.message_format,
+        "Sloth was built without LLVM, code can't be generated",
+    );
+}
+
+#[cfg(not(feature = "llvm"))]
+fn compile(input: &InputArgs, _: &CodegenArgs, _: &[SourceFile], _: &[Module], _: Emit, _: &Path) {
+    fail(
+        input.message_format,
+        "Sloth was built without LLVM, code can't be generated",
+    );
+}
+
+/// Standard library used by the program, None if it is disabled.
+fn std_lib(input: &InputArgs) -> Option<StdLib> {
+    if input.no_std {
+        return None;
+    }
+
+    Some(StdLib::locate().unwrap_or_else(|err| fail(input.message_format, err)))
+}
+
+/// Default output path is the name of the last source file in the current
+/// directory, with the extension of the output kind.
+fn default_output(input: &InputArgs, emit: Emit) -> Option<PathBuf> {
+    let extension = emit.extension()?;
+    let stem = input.sources().last()?.file_stem()?;
+    Some(PathBuf::from(stem).with_extension(extension))
+}
+
+fn report_errors(files: &[SourceFile], format: MessageFormat, diagnostics: Diagnostics) -> ! {
+    diagnostics.emit(files, format);
+    process::exit(1);
+}
+
+fn fail(format: MessageFormat, message: impl Display) -> ! {
+    Diagnostic::error(message.to_string()).emit(&[], format);
+    process::exit(1);
 }
 
 fn mk_symtable() -> SymbolTable {
@@ -101,64 +440,55 @@ fn mk_symtable() -> SymbolTable {
     global_symtable.insert("Bool".into(), Symbol::Type(Type::Boolean));
     global_symtable.insert("String".into(), Symbol::Type(Type::String));
 
-    // Inputs aren't type checked but outputs are
-    let dummyi = Symbol::Value(ValueSymbol {
-        typ: Type::Function {
-            inputs: vec![],
-            output: Box::new(Type::Integer),
-        },
-        id: 0,
-        mutable: true,
-    });
-
-    let dummyf = Symbol::Value(ValueSymbol {
-        typ: Type::Function {
-            inputs: vec![],
-            output: Box::new(Type::Float),
-        },
-        id: 0,
-        mutable: true,
-    });
-
-    let dummyb = Symbol::Value(ValueSymbol {
-        typ: Type::Function {
-            inputs: vec![],
-            output: Box::new(Type::Boolean),
-        },
-        id: 0,
-        mutable: true,
-    });
-
-    let dummys = Symbol::Value(ValueSymbol {
-        typ: Type::Function {
-            inputs: vec![],
-            output: Box::new(Type::Boolean),
-        },
-        id: 0,
-        mutable: true,
-    });
-
-    global_symtable.insert("vlen".into(), dummyi.clone());
-
-    global_symtable.insert("vpushi".into(), dummyi.clone());
-    global_symtable.insert("vpushf".into(), dummyf.clone());
-    global_symtable.insert("vpushb".into(), dummyb.clone());
-    global_symtable.insert("vpushs".into(), dummys.clone());
-
-    global_symtable.insert("vpopi".into(), dummyi.clone());
-    global_symtable.insert("vpopf".into(), dummyf.clone());
-    global_symtable.insert("vpopb".into(), dummyb.clone());
-    global_symtable.insert("vpops".into(), dummys.clone());
+    // Vector intrinsics are generic over the type of the elements, they are
+    // generated for every type they are used with
+    let element = || Type::Param("T".to_owned());
+    let vector = || Type::Array {
+        typ: Box::new(element()),
+    };
 
-    global_symtable.insert("vgeti".into(), dummyi.clone());
-    global_symtable.insert("vgetf".into(), dummyf.clone());
-    global_symtable.insert("vgetb".into(), dummyb.clone());
-    global_symtable.insert("vgets".into(), dummys.clone());
+    let intrinsics = [
+        ("vlen", vec![("vector", vector())], Type::Integer),
+        (
+            "vpush",
+            vec![("vector", vector()), ("value", element())],
+            Type::Void,
+        ),
+        ("vpop", vec![("vector", vector())], element()),
+        (
+            "vget",
+            vec![("vector", vector()), ("index", Type::Integer)],
+            element(),
+        ),
+        (
+            "vset",
+            vec![
+                ("vector", vector()),
+                ("index", Type::Integer),
+                ("value", element()),
+            ],
+            Type::Void,
+        ),
+    ];
 
-    global_symtable.insert("vseti".into(), dummyi);
-    global_symtable.insert("vsetf".into(), dummyf);
-    global_symtable.insert("vsetb".into(), dummyb);
-    global_symtable.insert("vsets".into(), dummys);
+    for (name, inputs, output) in intrinsics {
+        let (names, inputs) = inputs
+            .into_iter()
+            .map(|(name, typ)| (name.to_owned(), typ))
+            .unzip();
+        let symbol = Symbol::Value(ValueSymbol {
+            typ: Type::Function {
+                inputs,
+                output: Box::new(output),
+            },
+            id: global_symtable.reserve_id(),
+            mutable: false,
+            span: None,
+            constant: None,
+            inputs: names,
+        });
+        global_symtable.insert(name.into(), symbol);
+    }
 
     global_symtable
 }
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use itertools::Itertools;
+
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Lexer, Span};
+use crate::parser::ast::{Function, FunctionKind, Stmt, StmtKind};
+use crate::parser::{AstParser, ParsingError};
+use crate::symtable::SymbolTable;
+
+/// Index of a source file inside of a [`ModuleLoader`].
+pub type FileId = usize;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ModuleError {
+    #[error("Error while reading '{}': {1}", .0.display())]
+    Io(PathBuf, io::Error),
+    #[error("Error while importing '{}': {2}", .1.display())]
+    Import(Span, PathBuf, io::Error),
+    #[error("Could not parse '{}'", .0.display())]
+    Parsing(PathBuf, Vec<ParsingError>),
+    #[error("Circular import of module '{}'", .0.display())]
+    CircularImport(PathBuf),
+    /// Functions and globals are linked by their name, so a name can only be
+    /// defined once in a program.
+    #[error("'{0}' is defined more than once")]
+    DuplicateDefinition(String, Span, Span),
+}
+
+impl ModuleError {
+    /// Diagnostics describing the error, there is one for every syntax error.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            ModuleError::Import(span, path, err) => {
+                vec![
+                    Diagnostic::error(format!("Could not import '{}'", path.display()))
+                        .with_primary(*span, err.to_string()),
+                ]
+            }
+            ModuleError::Parsing(_, errors) => errors.iter().map(Diagnostic::from).collect(),
+            ModuleError::DuplicateDefinition(name, span, first) => {
+                vec![Diagnostic::error(self.to_string())
+                    .with_primary(*span, "defined again here")
+                    .with_secondary(*first, format!("'{name}' is first defined here"))
+                    .with_note("names of functions and globals are shared by every module")]
+            }
+            _ => vec![Diagnostic::error(self.to_string())],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+#[derive(Debug)]
+pub struct Module {
+    pub file: FileId,
+    pub ast: Stmt,
+    imports: Vec<FileId>,
+}
+
+/// Loads source files and everything they import into modules. Each module has
+/// its own symbol table which is a child of the global symbol table.
+pub struct ModuleLoader {
+    global: SymbolTable,
+    files: Vec<SourceFile>,
+    canonical: Vec<PathBuf>,
+    modules: Vec<Module>,
+    roots: Vec<FileId>,
+    prelude: Vec<FileId>,
+}
+
+impl ModuleLoader {
+    pub fn new(global: SymbolTable) -> Self {
+        Self {
+            global,
+            files: Vec::new(),
+            canonical: Vec::new(),
+            modules: Vec::new(),
+            roots: Vec::new(),
+            prelude: Vec::new(),
+        }
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Load a file given on the command line along with everything it imports.
+    /// Other roots have to be imported with `use`.
+    pub fn load_root(&mut self, path: impl AsRef<Path>) -> Result<FileId, ModuleError> {
+        let file = self.load(path.as_ref())?;
+
+        if !self.roots.contains(&file) {
+            self.roots.push(file);
+        }
+
+        Ok(file)
+    }
+
+    /// Load a file of the standard library. Every module implicitly imports the
+    /// standard library, and its files import the files loaded before them.
+    pub fn load_prelude(&mut self, path: impl AsRef<Path>) -> Result<FileId, ModuleError> {
+        let file = self.load_root(path)?;
+
+        if !self.prelude.contains(&file) {
+            let implicit = self.prelude.clone();
+            self.module_mut(file).imports.extend(implicit);
+            self.prelude.push(file);
+        }
+
+        Ok(file)
+    }
+
+    /// Functions and globals defined more than once among every loaded module.
+    /// Foreign functions can be declared by multiple modules.
+    pub fn duplicate_definitions(&self) -> Vec<ModuleError> {
+        let mut errors = Vec::new();
+        let mut defined = HashMap::new();
+
+        for module in &self.modules {
+            let StmtKind::Block(stmts) = &module.ast.kind else {
+                continue;
+            };
+
+            for stmt in stmts {
+                let (name, foreign) = match &stmt.kind {
+                    StmtKind::DefineFunction(Function {
+                        identifier, kind, ..
+                    }) => (identifier, matches!(kind, FunctionKind::Foreign)),
+                    StmtKind::DefineVariable { identifier, .. }
+                    | StmtKind::DefineValue { identifier, .. }
+                    | StmtKind::DefineConst { identifier, .. } => (identifier, false),
+                    _ => continue,
+                };
+
+                match defined.get(name) {
+                    Some(&(_, true)) if foreign => (),
+                    Some(&(first, _)) => errors.push(ModuleError::DuplicateDefinition(
+                        name.clone(),
+                        stmt.span,
+                        first,
+                    )),
+                    None => {
+                        defined.insert(name, (stmt.span, foreign));
+                    }
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Link the symbol tables of every module to the modules they import and
+    /// return the modules ordered so that every module comes after the modules
+    /// it imports.
+    pub fn finish(mut self) -> Result<(Vec<SourceFile>, Vec<Module>), ModuleError> {
+        for module in &mut self.modules {
+            if !self.prelude.contains(&module.file) {
+                module.imports.extend(&self.prelude);
+            }
+        }
+
+        let order = self.dependency_order()?;
+
+        for module in &self.modules {
+            let imports = module
+                .imports
+                .iter()
+                .unique()
+                .map(|it| self.module(*it).ast.symtable.clone())
+                .collect_vec();
+
+            module.ast.symtable.set_imports(&imports);
+        }
+
+        let mut modules = self.modules.into_iter().map(Some).collect_vec();
+        let modules = order
+            .into_iter()
+            .map(|it| modules[it].take().unwrap())
+            .collect_vec();
+
+        Ok((self.files, modules))
+    }
+
+    fn load(&mut self, path: &Path) -> Result<FileId, ModuleError> {
+        let canonical = path
+            .canonicalize()
+            .map_err(|err| ModuleError::Io(path.to_owned(), err))?;
+
+        // Files are identified by their canonical path so a module imported from
+        // multiple places is only loaded once
+        if let Some(file) = self.canonical.iter().position(|it| *it == canonical) {
+            return Ok(file);
+        }
+
+        let source =
+            fs::read_to_string(&canonical).map_err(|err| ModuleError::Io(path.to_owned(), err))?;
+
+        let file = self.files.len();
+        self.canonical.push(canonical);
+        self.files.push(SourceFile {
+            path: path.to_owned(),
+            source,
+        });
+
+        // Parsing the module into its own symbol table
+        let tokens = Lexer::new(&self.files[file].source)
+            .with_file(file)
+            .collect_vec();
+        let ast = AstParser::parse(tokens, self.global.make_child())
+            .map_err(|errors| ModuleError::Parsing(path.to_owned(), errors))?;
+
+        // Collect the imports of the module before loading them
+        let uses = match &ast.kind {
+            StmtKind::Block(stmts) => stmts
+                .iter()
+                .filter_map(|it| match &it.kind {
+                    StmtKind::Use(path) => Some((path.clone(), it.span)),
+                    _ => None,
+                })
+                .collect_vec(),
+            _ => Vec::new(),
+        };
+
+        self.modules.push(Module {
+            file,
+            ast,
+            imports: Vec::new(),
+        });
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for (segments, span) in uses {
+            let mut import = segments.iter().collect::<PathBuf>();
+            import.set_extension("sloth");
+
+            // Failing to read an imported file is reported at the `use` statement
+            let imported = self
+                .load(&directory.join(import))
+                .map_err(|err| match err {
+                    ModuleError::Io(path, err) => ModuleError::Import(span, path, err),
+                    err => err,
+                })?;
+            self.module_mut(file).imports.push(imported);
+        }
+
+        Ok(file)
+    }
+
+    fn dependency_order(&self) -> Result<Vec<usize>, ModuleError> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            Visiting,
+            Done,
+        }
+
+        fn visit(
+            this: &ModuleLoader,
+            file: FileId,
+            states: &mut [State],
+            order: &mut Vec<usize>,
+        ) -> Result<(), ModuleError> {
+            let index = this.index_of(file);
+            match states[index] {
+                State::Done => return Ok(()),
+                State::Visiting => {
+                    return Err(ModuleError::CircularImport(this.files[file].path.clone()))
+                }
+                State::Unvisited => (),
+            }
+
+            states[index] = State::Visiting;
+            for import in &this.modules[index].imports {
+                visit(this, *import, states, order)?;
+            }
+            states[index] = State::Done;
+
+            order.push(index);
+            Ok(())
+        }
+
+        let mut states = vec![State::Unvisited; self.modules.len()];
+        let mut order = Vec::new();
+        for root in &self.roots {
+            visit(self, *root, &mut states, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    fn index_of(&self, file: FileId) -> usize {
+        self.modules.iter().position(|it| it.file == file).unwrap()
+    }
+
+    fn module(&self, file: FileId) -> &Module {
+        &self.modules[self.index_of(file)]
+    }
+
+    fn module_mut(&mut self, file: FileId) -> &mut Module {
+        let index = self.index_of(file);
+        &mut self.modules[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use itertools::Itertools;
+
+    use super::{ModuleError, ModuleLoader};
+    use crate::analysis::analyze;
+    use crate::symtable::{Symbol, SymbolTable, Type};
+
+    /// Write the files into a fresh directory for the test and return it.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("sloth-modules-{test}"));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        for (name, source) in files {
+            fs::write(directory.join(name), source).unwrap();
+        }
+
+        directory
+    }
+
+    #[test]
+    fn roots_load_in_any_order() {
+        let directory = write_files("roots", &[
+            ("util.sloth", "fn helper() Int { return 1; }"),
+            ("main.sloth", "use util; fn main() Int { return helper(); }"),
+        ]);
+
+        for order in [["main.sloth", "util.sloth"], ["util.sloth", "main.sloth"]] {
+            let mut loader = ModuleLoader::new(SymbolTable::new());
+            for name in order {
+                loader.load_root(directory.join(name)).unwrap();
+            }
+
+            assert!(loader.finish().is_ok());
+        }
+    }
+
+    #[test]
+    fn nested_modules_use_the_prelude() {
+        let directory = write_files("prelude", &[
+            ("std.sloth", "fn greet() Int { return 1; }"),
+            ("util.sloth", "fn helper() Int { return greet(); }"),
+            ("main.sloth", "use util; fn main() Int { return helper(); }"),
+        ]);
+
+        let mut global = SymbolTable::new();
+        global.insert("Int".into(), Symbol::Type(Type::Integer));
+
+        let mut loader = ModuleLoader::new(global);
+        loader.load_prelude(directory.join("std.sloth")).unwrap();
+        loader.load_root(directory.join("main.sloth")).unwrap();
+        let (_, mut modules) = loader.finish().unwrap();
+
+        // The prelude comes before the modules using it
+        let files = modules.iter().map(|it| it.file).collect_vec();
+        assert_eq!(files, [0, 2, 1]);
+
+        for module in &mut modules {
+            analyze(&mut module.ast).unwrap();
+        }
+    }
+
+    #[test]
+    fn duplicate_definitions_across_modules() {
+        let directory = write_files("duplicates", &[
+            ("util.sloth", "fn helper() Int { return 1; }"),
+            (
+                "main.sloth",
+                "use util; fn helper() Int { return 2; } fn main() Int { return helper(); }",
+            ),
+        ]);
+
+        let mut loader = ModuleLoader::new(SymbolTable::new());
+        loader.load_root(directory.join("main.sloth")).unwrap();
+        let errors = loader.duplicate_definitions();
+
+        println!("Errors:\n{errors:#?}\n\n");
+
+        assert!(matches!(
+            errors.as_slice(),
+            [ModuleError::DuplicateDefinition(name, ..)] if name == "helper"
+        ));
+    }
+}
@@ -1,6 +1,8 @@
 use std::fmt::Display;
 
-use crate::lexer::{self, TokenType};
+use itertools::Itertools;
+
+use crate::lexer::{self, Span, Token, TokenType};
 use crate::parser::ParsingError;
 use crate::symtable::{SymbolTable, Type};
 
@@ -22,10 +24,10 @@ impl<'a> AstNode<'a> {
         children.into_iter()
     }
 
-    pub fn line(&self) -> u32 {
+    pub fn span(&self) -> Span {
         match self {
-            Self::Expr(expr) => expr.line,
-            Self::Stmt(stmt) => stmt.line,
+            Self::Expr(expr) => expr.span,
+            Self::Stmt(stmt) => stmt.span,
         }
     }
 }
@@ -33,14 +35,13 @@ impl<'a> AstNode<'a> {
 #[derive(Clone, Debug)]
 pub struct Expr {
     pub id: i32,
-    pub line: u32,
+    pub span: Span,
     pub kind: ExprKind,
     pub symtable: SymbolTable,
 
     /// Type of the expression. If None it means the type hasn't yet been
     /// checked.
     pub typ: Option<Type>,
-    pub is_const: bool,
 }
 
 impl PartialEq for Expr {
@@ -50,40 +51,42 @@ impl PartialEq for Expr {
 }
 
 impl Expr {
-    pub fn new(id: i32, line: u32, kind: ExprKind, symtable: SymbolTable) -> Self {
-        /// Recursivly check if a expression is constant
-        fn is_const(kind: &ExprKind) -> bool {
-            match kind {
-                ExprKind::Literal(_) => true,
-                ExprKind::Grouping(child) => is_const(&child.kind),
-                ExprKind::BinaryOp { lhs, rhs, .. } => is_const(&lhs.kind) && is_const(&rhs.kind),
-                ExprKind::UnaryOp { value, .. } => is_const(&value.kind),
-                ExprKind::Call { .. } | ExprKind::Identifier(_) => false,
-            }
-        }
-
-        let is_const = is_const(&kind);
-
+    pub fn new(id: i32, span: Span, kind: ExprKind, symtable: SymbolTable) -> Self {
         Self {
             id,
-            line,
+            span,
             kind,
             symtable,
 
             typ: None,
-            is_const,
         }
     }
 
     /// Useful for testing
     pub fn without_table(id: i32, kind: ExprKind) -> Self {
-        Self::new(id, 0, kind, SymbolTable::new())
+        Self::new(id, Span::default(), kind, SymbolTable::new())
     }
 
     pub fn as_node(&self) -> AstNode {
         AstNode::Expr(self)
     }
 
+    /// Whether the expression refers to a location that can be assigned to,
+    /// which is a variable or a field of one, an element of an array or the
+    /// value behind a pointer.
+    pub fn is_place(&self) -> bool {
+        match &self.kind {
+            ExprKind::Identifier(_) => true,
+            ExprKind::FieldAccess { value, .. } => value.is_place(),
+            ExprKind::Index { .. } => true,
+            ExprKind::UnaryOp {
+                op: UnaryOp::Dereference,
+                ..
+            } => true,
+            _ => false,
+        }
+    }
+
     pub fn children(&self) -> impl Iterator<Item = AstNode> {
         let mut children = Vec::new();
 
@@ -98,6 +101,19 @@ impl Expr {
                 children.push(callee.as_node());
                 children.extend(args.iter().map(Expr::as_node));
             }
+            ExprKind::StructLiteral { fields, .. } => {
+                children.extend(fields.iter().map(|(_, value)| value.as_node()));
+            }
+            ExprKind::FieldAccess { value, .. } => children.push(value.as_node()),
+            ExprKind::Index { value, index } => {
+                children.push(value.as_node());
+                children.push(index.as_node());
+            }
+            ExprKind::Variant { args, .. } => children.extend(args.iter().map(Expr::as_node)),
+            ExprKind::Match { value, arms } => {
+                children.push(value.as_node());
+                children.extend(arms.iter().map(|it| it.body.as_node()));
+            }
             _ => (),
         }
 
@@ -123,12 +139,39 @@ pub enum ExprKind {
         callee: Box<Expr>,
         args: Vec<Expr>,
     },
+    /// Construction of a struct, `Point { x: 1, y: 2 }`. Fields are in the
+    /// order they were written in.
+    StructLiteral {
+        identifier: String,
+        fields: Vec<(String, Expr)>,
+    },
+    FieldAccess {
+        value: Box<Expr>,
+        field: String,
+    },
+    /// Element of an array, `values[i]`.
+    Index {
+        value: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// Construction of an enum variant, `Shape::Rect(1.0, 2.0)`. Variants
+    /// without fields are written without parentheses, `Direction::Up`.
+    Variant {
+        enumeration: String,
+        variant: String,
+        args: Vec<Expr>,
+    },
+    /// Match used as an expression, every arm evaluates to a value.
+    Match {
+        value: Box<Expr>,
+        arms: Vec<MatchArm<Expr>>,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub struct Stmt {
     pub id: i32,
-    pub line: u32,
+    pub span: Span,
     pub kind: StmtKind,
     pub symtable: SymbolTable,
 }
@@ -140,10 +183,10 @@ impl PartialEq for Stmt {
 }
 
 impl Stmt {
-    pub fn new(id: i32, line: u32, kind: StmtKind, symtable: SymbolTable) -> Self {
+    pub fn new(id: i32, span: Span, kind: StmtKind, symtable: SymbolTable) -> Self {
         Self {
             id,
-            line,
+            span,
             kind,
             symtable,
         }
@@ -153,7 +196,7 @@ impl Stmt {
     pub fn without_table(id: i32, kind: StmtKind) -> Self {
         Self {
             id,
-            line: 0,
+            span: Span::default(),
             kind,
             symtable: SymbolTable::new(),
         }
@@ -182,27 +225,39 @@ impl Stmt {
                     children.push(else_then.as_node());
                 }
             }
-            StmtKind::WhileStmt { condition, body } => {
+            StmtKind::WhileStmt {
+                condition, body, ..
+            } => {
                 children.push(condition.as_node());
                 children.push(body.as_node());
             }
-            StmtKind::ForStmt {
-                iterator,
-                identifier: _,
-                body,
-            } => {
+            StmtKind::ForStmt { iterator, body, .. } => {
                 children.push(iterator.as_node());
                 children.push(body.as_node());
             }
+            StmtKind::Loop { body, .. } => children.push(body.as_node()),
             StmtKind::DefineVariable { value, .. } => children.push(value.as_node()),
             StmtKind::DefineValue { value, .. } => children.push(value.as_node()),
-            StmtKind::AssignVariable { value, .. } => children.push(value.as_node()),
+            StmtKind::DefineConst { value, .. } => children.push(value.as_node()),
+            StmtKind::Assign { target, value, .. } => {
+                children.push(target.as_node());
+                children.push(value.as_node());
+            }
             StmtKind::DefineFunction(Function { kind, .. }) => {
                 if let FunctionKind::Normal { body } = kind {
                     children.push(body.as_node())
                 }
             }
-            StmtKind::Return(value) => children.push(value.as_node()),
+            StmtKind::Match { value, arms } => {
+                children.push(value.as_node());
+                children.extend(arms.iter().map(|it| it.body.as_node()));
+            }
+            StmtKind::Return(value) => children.extend(value.iter().map(Expr::as_node)),
+            StmtKind::Break(_)
+            | StmtKind::Continue(_)
+            | StmtKind::DefineStruct(_)
+            | StmtKind::DefineEnum(_)
+            | StmtKind::Use(_) => (),
         }
 
         children.into_iter()
@@ -220,15 +275,29 @@ pub enum StmtKind {
         if_then: Box<Stmt>,
         else_then: Option<Box<Stmt>>,
     },
+    /// Loops can be labeled, `outer: while x < 10 { ... }`, so that `break`
+    /// and `continue` can refer to loops they are nested in.
     WhileStmt {
+        label: Option<String>,
         condition: Expr,
         body: Box<Stmt>,
     },
     ForStmt {
+        label: Option<String>,
         iterator: Expr,
         identifier: String,
         body: Box<Stmt>,
     },
+    /// Loop without a condition, it only ends by a break or return.
+    Loop {
+        label: Option<String>,
+        body: Box<Stmt>,
+    },
+    /// Exit the innermost loop, or the loop with the label.
+    Break(Option<String>),
+    /// Skip to the next iteration of the innermost loop, or of the loop with
+    /// the label.
+    Continue(Option<String>),
     DefineVariable {
         identifier: String,
         value: Expr,
@@ -239,20 +308,47 @@ pub enum StmtKind {
         value: Expr,
         typ: Option<TypeIdentifier>,
     },
-    AssignVariable {
+    /// A constant, `const NAME: Type = value;`. The value is evaluated at
+    /// compile time, so it can only be made of literals, operators and other
+    /// constants.
+    DefineConst {
         identifier: String,
         value: Expr,
+        typ: TypeIdentifier,
+    },
+    /// Assignment to a place such as a variable, `op` is the operator of a
+    /// compound assignment such as `x += 1;`.
+    Assign {
+        target: Expr,
+        op: Option<BinaryOp>,
+        value: Expr,
     },
     /// A function definition. Output is None when the function returns nothing
     /// meaning void, otherwise it is the name of the type the function
     /// returns.
     DefineFunction(Function),
-    Return(Expr),
+    DefineStruct(Struct),
+    DefineEnum(Enum),
+    /// Match used as a statement, the body of every arm is either a block or
+    /// an expression statement.
+    Match {
+        value: Expr,
+        arms: Vec<MatchArm<Stmt>>,
+    },
+    /// Return from the current function, the value is left out in functions
+    /// returning Void.
+    Return(Option<Expr>),
+    /// Import of another module. The path is relative to the directory of the
+    /// importing file, `use foo::bar;` imports `foo/bar.sloth`.
+    Use(Vec<String>),
 }
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct Function {
     pub identifier: String,
+    /// Type parameters, generic functions are generated once for every
+    /// combination of types they are called with.
+    pub params: Vec<String>,
     pub inputs: Vec<FunctionInput>,
     pub output: Option<TypeIdentifier>,
     pub kind: FunctionKind,
@@ -270,20 +366,105 @@ pub struct FunctionInput {
     pub typ: TypeIdentifier,
 }
 
+#[derive(PartialEq, Clone, Debug)]
+pub struct Struct {
+    pub identifier: String,
+    pub params: Vec<String>,
+    pub fields: Vec<StructField>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct StructField {
+    pub identifier: String,
+    pub typ: TypeIdentifier,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Enum {
+    pub identifier: String,
+    pub variants: Vec<EnumVariant>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct EnumVariant {
+    pub identifier: String,
+    pub fields: Vec<TypeIdentifier>,
+}
+
+/// Arm of a match, the body is only run if the pattern matches. Symbols bound
+/// by the pattern are added to the symbol table of the body.
+#[derive(Clone, Debug)]
+pub struct MatchArm<T> {
+    pub pattern: Pattern,
+    pub pattern_span: Span,
+    pub body: T,
+}
+
+impl<T: PartialEq> PartialEq for MatchArm<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.body == other.body
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Pattern {
+    /// `_`, matches every value.
+    Wildcard,
+    /// `Shape::Rect(width, height)`, binds the fields of the variant to names.
+    /// Fields bound to `_` are ignored.
+    Variant {
+        enumeration: String,
+        variant: String,
+        bindings: Vec<String>,
+    },
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Variant {
+                enumeration,
+                variant,
+                bindings,
+            } => {
+                write!(f, "{enumeration}::{variant}")?;
+                if !bindings.is_empty() {
+                    write!(f, "({})", bindings.join(", "))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub struct TypeIdentifier {
     pub name: String,
+    /// Type arguments of a generic type, `Int` in `Stack<Int>`.
+    pub args: Vec<TypeIdentifier>,
     pub is_list: bool,
+    /// Pointer to a value of the type, `*Int`.
+    pub is_pointer: bool,
 }
 
 impl Display for TypeIdentifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_pointer {
+            write!(f, "*")?;
+        }
+
         if self.is_list {
             write!(f, "[")?;
         }
 
         write!(f, "{}", self.name)?;
 
+        if !self.args.is_empty() {
+            write!(f, "<{}>", self.args.iter().join(", "))?;
+        }
+
         if self.is_list {
             write!(f, "]")?;
         }
@@ -300,6 +481,9 @@ pub enum Literal {
     Character(char),
     String(String),
     Array(Vec<Expr>),
+    /// Array of a value repeated a number of times, `[0; 10]`. The number has
+    /// to be a constant.
+    Repeat(Box<Expr>, Box<Expr>),
 }
 
 impl From<lexer::Literal> for Literal {
@@ -333,7 +517,7 @@ impl Display for Literal {
                 "\\\"{}\\\"",
                 s.replace('\"', "\\\"").replace("\\n", "\\\\n")
             ),
-            Literal::Array(..) => "<Array>".to_string(),
+            Literal::Array(..) | Literal::Repeat(..) => "<Array>".to_string(),
         };
 
         write!(f, "{value}")
@@ -348,6 +532,10 @@ pub enum BinaryOp {
     Mul,
     Div,
     Mod,
+    Pow,
+
+    Shl,
+    Shr,
 
     Lt,
     Gt,
@@ -362,17 +550,21 @@ pub enum BinaryOp {
     Range,
 }
 
-impl TryFrom<TokenType> for BinaryOp {
+impl TryFrom<&Token<'_>> for BinaryOp {
     type Error = ParsingError;
 
-    fn try_from(value: TokenType) -> Result<Self, Self::Error> {
-        let operation = match value {
+    fn try_from(value: &Token<'_>) -> Result<Self, Self::Error> {
+        let operation = match value.tt {
             TokenType::Plus => Self::Add,
             TokenType::PlusPlus => Self::Con,
             TokenType::Minus => Self::Sub,
             TokenType::Star => Self::Mul,
             TokenType::Slash => Self::Div,
             TokenType::Perc => Self::Mod,
+            TokenType::StarStar => Self::Pow,
+
+            TokenType::LtLt => Self::Shl,
+            TokenType::GtGt => Self::Shr,
 
             TokenType::Lt => Self::Lt,
             TokenType::Gt => Self::Gt,
@@ -386,13 +578,35 @@ impl TryFrom<TokenType> for BinaryOp {
 
             TokenType::DotDot => Self::Range,
 
-            _ => return Err(ParsingError::InvalidOp),
+            _ => return Err(ParsingError::InvalidOp(value.span())),
         };
 
         Ok(operation)
     }
 }
 
+impl BinaryOp {
+    /// Operator applied by a compound assignment token such as `+=`.
+    pub fn compound(tt: &TokenType) -> Option<Self> {
+        let operation = match tt {
+            TokenType::PlusEq => Self::Add,
+            TokenType::PlusPlusEq => Self::Con,
+            TokenType::MinusEq => Self::Sub,
+            TokenType::StarEq => Self::Mul,
+            TokenType::SlashEq => Self::Div,
+            TokenType::PercEq => Self::Mod,
+            TokenType::StarStarEq => Self::Pow,
+
+            TokenType::LtLtEq => Self::Shl,
+            TokenType::GtGtEq => Self::Shr,
+
+            _ => return None,
+        };
+
+        Some(operation)
+    }
+}
+
 impl Display for BinaryOp {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let value = match self {
@@ -402,6 +616,10 @@ impl Display for BinaryOp {
             BinaryOp::Mul => "*",
             BinaryOp::Div => "/",
             BinaryOp::Mod => "%",
+            BinaryOp::Pow => "**",
+
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
 
             BinaryOp::Lt => "<",
             BinaryOp::Gt => ">",
@@ -429,18 +647,18 @@ pub enum UnaryOp {
     Dereference,
 }
 
-impl TryFrom<TokenType> for UnaryOp {
+impl TryFrom<&Token<'_>> for UnaryOp {
     type Error = ParsingError;
 
-    fn try_from(value: TokenType) -> Result<Self, Self::Error> {
-        let operation = match value {
+    fn try_from(value: &Token<'_>) -> Result<Self, Self::Error> {
+        let operation = match value.tt {
             TokenType::Bang => Self::Not,
             TokenType::Minus => Self::Neg,
 
             TokenType::Star => Self::Reference,
             TokenType::At => Self::Dereference,
 
-            _ => return Err(ParsingError::InvalidOp),
+            _ => return Err(ParsingError::InvalidOp(value.span())),
         };
 
         Ok(operation)
@@ -1,4 +1,4 @@
-use super::ast::{Expr, Literal, UnaryOp};
+use super::ast::{Expr, Literal, MatchArm, Pattern, UnaryOp};
 use super::AstParser;
 use crate::lexer::TokenType;
 use crate::parser::ast::{BinaryOp, ExprKind};
@@ -9,6 +9,25 @@ impl<'a> AstParser<'a> {
         self.logical_or()
     }
 
+    /// Parse an expression followed by a block, such as the condition of an if
+    /// statement. Struct literals are only allowed inside of delimiters as
+    /// their braces would be mistaken for the block.
+    pub(super) fn condition(&mut self) -> Result<Expr, ParsingError> {
+        let previous = std::mem::replace(&mut self.struct_literals, false);
+        let expr = self.expression();
+        self.struct_literals = previous;
+        expr
+    }
+
+    /// Parse an expression inside of parentheses, brackets or braces, where
+    /// struct literals are always allowed.
+    fn enclosed_expression(&mut self) -> Result<Expr, ParsingError> {
+        let previous = std::mem::replace(&mut self.struct_literals, true);
+        let expr = self.expression();
+        self.struct_literals = previous;
+        expr
+    }
+
     fn unary(&mut self) -> Result<Expr, ParsingError> {
         if !self.eof()
             && matches!(
@@ -16,8 +35,8 @@ impl<'a> AstParser<'a> {
                 TokenType::Bang | TokenType::Minus | TokenType::Star | TokenType::At
             )
         {
-            let operator_tt = self.advance().unwrap().tt.clone();
-            let operator = UnaryOp::try_from(operator_tt)?;
+            let start = self.peek_span();
+            let operator = UnaryOp::try_from(self.advance().unwrap())?;
 
             let value = self.unary()?;
 
@@ -28,7 +47,7 @@ impl<'a> AstParser<'a> {
 
             return Ok(Expr::new(
                 self.reserve_id(),
-                self.line,
+                self.span_from(start),
                 kind,
                 self.top.clone(),
             ));
@@ -40,26 +59,54 @@ impl<'a> AstParser<'a> {
     fn call(&mut self) -> Result<Expr, ParsingError> {
         let mut expr = self.primary()?;
 
-        if !self.eof() && self.peek().tt == TokenType::OpeningParen {
-            self.consume(TokenType::OpeningParen, "Expected '('")?;
+        // Calls, field accesses and indexing can be chained, `foo().bar[0]`
+        loop {
+            let start = expr.span;
+            let kind = match self.peek().tt {
+                TokenType::OpeningParen => {
+                    self.consume(TokenType::OpeningParen, "Expected '('")?;
+
+                    let mut arguments = Vec::new();
+                    while !self.eof() && self.peek().tt != TokenType::ClosingParen {
+                        arguments.push(self.enclosed_expression()?);
+                        if !self.advance_if_eq(&TokenType::Comma) {
+                            break;
+                        }
+                    }
+
+                    self.consume(TokenType::ClosingParen, "Expected ')'")?;
 
-            let mut arguments = Vec::new();
-            while !self.eof() && self.peek().tt != TokenType::ClosingParen {
-                arguments.push(self.expression()?);
-                if !self.advance_if_eq(&TokenType::Comma) {
-                    break;
+                    ExprKind::Call {
+                        callee: Box::new(expr),
+                        args: arguments,
+                    }
                 }
-            }
+                TokenType::Dot => {
+                    self.consume(TokenType::Dot, "Expected '.'")?;
+                    let field = self.consume_identifier()?;
 
-            self.consume(TokenType::ClosingParen, "Expected ')'")?;
+                    ExprKind::FieldAccess {
+                        value: Box::new(expr),
+                        field,
+                    }
+                }
+                TokenType::OpeningBracket => {
+                    self.consume(TokenType::OpeningBracket, "Expected '['")?;
+                    let index = self.enclosed_expression()?;
+                    self.consume(TokenType::ClosingBracket, "Expected ']'")?;
+
+                    ExprKind::Index {
+                        value: Box::new(expr),
+                        index: Box::new(index),
+                    }
+                }
+                _ => break,
+            };
 
             expr = Expr::new(
                 self.reserve_id(),
-                self.line,
-                ExprKind::Call {
-                    callee: Box::new(expr),
-                    args: arguments,
-                },
+                self.span_from(start),
+                kind,
                 self.top.clone(),
             );
         }
@@ -68,14 +115,40 @@ impl<'a> AstParser<'a> {
     }
 
     fn primary(&mut self) -> Result<Expr, ParsingError> {
-        let kind = match self.advance().unwrap().tt.clone() {
-            TokenType::Literal(literal) => ExprKind::Literal(literal.into()),
-            TokenType::Identifier(identifier) => ExprKind::Identifier(identifier),
+        let start = self.peek_span();
+        let kind = match self.peek().tt.clone() {
+            TokenType::Literal(literal) => {
+                self.advance();
+                ExprKind::Literal(literal.into())
+            }
+            TokenType::Identifier(identifier) => {
+                self.advance();
+
+                if self.peek().tt == TokenType::ColonColon {
+                    self.variant(identifier)?
+                } else if self.struct_literals && self.peek().tt == TokenType::OpeningBrace {
+                    self.struct_literal(identifier)?
+                } else {
+                    ExprKind::Identifier(identifier)
+                }
+            }
+
+            TokenType::Match => self.match_expr()?,
 
             TokenType::OpeningBracket => {
+                self.advance();
+
                 let mut contents = Vec::new();
+                let mut count = None;
                 while !self.eof() && self.peek().tt != TokenType::ClosingBracket {
-                    contents.push(self.expression()?);
+                    contents.push(self.enclosed_expression()?);
+
+                    // The first value can be repeated instead, `[0; 10]`
+                    if contents.len() == 1 && self.advance_if_eq(&TokenType::SemiColon) {
+                        count = Some(self.enclosed_expression()?);
+                        break;
+                    }
+
                     if !self.advance_if_eq(&TokenType::Comma) {
                         break;
                     }
@@ -83,25 +156,176 @@ impl<'a> AstParser<'a> {
 
                 self.consume(TokenType::ClosingBracket, "Expected ']'")?;
 
-                ExprKind::Literal(Literal::Array(contents))
+                match count {
+                    Some(count) => ExprKind::Literal(Literal::Repeat(
+                        Box::new(contents.remove(0)),
+                        Box::new(count),
+                    )),
+                    None => ExprKind::Literal(Literal::Array(contents)),
+                }
             }
 
             TokenType::OpeningParen => {
-                let expr = self.expression()?;
+                self.advance();
+
+                let expr = self.enclosed_expression()?;
                 self.consume(TokenType::ClosingParen, "Must end grouping with ')'")?;
                 ExprKind::Grouping(Box::new(expr))
             }
 
-            tt => return Err(ParsingError::UnexpectedToken(self.line, tt, "")),
+            // The token isn't consumed so error recovery can make use of it
+            tt => {
+                return Err(ParsingError::UnexpectedToken(
+                    start,
+                    tt,
+                    "Expected expression",
+                ))
+            }
         };
 
         Ok(Expr::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
+
+    /// Parse the fields of a struct literal, the name of the struct has already
+    /// been consumed.
+    fn struct_literal(&mut self, identifier: String) -> Result<ExprKind, ParsingError> {
+        self.consume(TokenType::OpeningBrace, "Expected '{'")?;
+
+        let mut fields = Vec::new();
+        while !self.eof() && self.peek().tt != TokenType::ClosingBrace {
+            let field = self.consume_identifier()?;
+            self.consume(TokenType::Colon, "Expected ':'")?;
+            let value = self.enclosed_expression()?;
+
+            fields.push((field, value));
+
+            if !self.advance_if_eq(&TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::ClosingBrace, "Expected '}'")?;
+
+        Ok(ExprKind::StructLiteral { identifier, fields })
+    }
+
+    /// Parse the variant and fields of an enum variant, the name of the enum
+    /// has already been consumed.
+    fn variant(&mut self, enumeration: String) -> Result<ExprKind, ParsingError> {
+        self.consume(TokenType::ColonColon, "Expected '::'")?;
+        let variant = self.consume_identifier()?;
+
+        let mut args = Vec::new();
+        if self.advance_if_eq(&TokenType::OpeningParen) {
+            while !self.eof() && self.peek().tt != TokenType::ClosingParen {
+                args.push(self.enclosed_expression()?);
+                if !self.advance_if_eq(&TokenType::Comma) {
+                    break;
+                }
+            }
+
+            self.consume(TokenType::ClosingParen, "Expected ')'")?;
+        }
+
+        Ok(ExprKind::Variant {
+            enumeration,
+            variant,
+            args,
+        })
+    }
+
+    fn match_expr(&mut self) -> Result<ExprKind, ParsingError> {
+        self.consume(TokenType::Match, "Expected match")?;
+
+        let value = self.condition()?;
+        self.consume(TokenType::OpeningBrace, "Expected '{'")?;
+
+        let mut arms = Vec::new();
+        while !self.eof() && self.peek().tt != TokenType::ClosingBrace {
+            let start = self.peek_span();
+            let pattern = self.pattern()?;
+            let pattern_span = self.span_from(start);
+            self.consume(TokenType::FatArrow, "Expected '=>'")?;
+
+            // Every arm gets its own scope for the symbols bound by its pattern
+            self.push_table();
+            let body = self.enclosed_expression();
+            self.pop_table();
+
+            arms.push(MatchArm {
+                pattern,
+                pattern_span,
+                body: body?,
+            });
+
+            if !self.advance_if_eq(&TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::ClosingBrace, "Expected '}'")?;
+
+        Ok(ExprKind::Match {
+            value: Box::new(value),
+            arms,
+        })
+    }
+
+    /// Parse the pattern of a match arm, either `_` or a variant of an enum.
+    pub(super) fn pattern(&mut self) -> Result<Pattern, ParsingError> {
+        let enumeration = self.consume_identifier()?;
+        if enumeration == "_" {
+            return Ok(Pattern::Wildcard);
+        }
+
+        self.consume(TokenType::ColonColon, "Expected '::'")?;
+        let variant = self.consume_identifier()?;
+
+        let mut bindings = Vec::new();
+        if self.advance_if_eq(&TokenType::OpeningParen) {
+            while !self.eof() && self.peek().tt != TokenType::ClosingParen {
+                bindings.push(self.consume_identifier()?);
+                if !self.advance_if_eq(&TokenType::Comma) {
+                    break;
+                }
+            }
+
+            self.consume(TokenType::ClosingParen, "Expected ')'")?;
+        }
+
+        Ok(Pattern::Variant {
+            enumeration,
+            variant,
+            bindings,
+        })
+    }
+}
+
+impl<'a> AstParser<'a> {
+    /// Exponentiation binds tighter than the other binary operators and is
+    /// right associative, `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    fn power(&mut self) -> Result<Expr, ParsingError> {
+        let base = self.unary()?;
+
+        if !self.advance_if_eq(&TokenType::StarStar) {
+            return Ok(base);
+        }
+
+        let exponent = self.power()?;
+        let span = base.span.to(exponent.span);
+        let kind = ExprKind::BinaryOp {
+            op: BinaryOp::Pow,
+            lhs: Box::new(base),
+            rhs: Box::new(exponent),
+        };
+
+        Ok(Expr::new(self.reserve_id(), span, kind, self.top.clone()))
+    }
 }
 
 // Macro to generate repetitive binary expressions. Things like addition,
@@ -112,17 +336,17 @@ macro_rules! binary_expr {
             let mut expr = self.$parent()?;
 
             while !self.eof() && matches!(self.peek().tt, $pattern) {
-                let operator_tt = self.advance().unwrap().tt.clone();
-                let operator = BinaryOp::try_from(operator_tt)?;
+                let operator = BinaryOp::try_from(self.advance().unwrap())?;
 
                 let rhs = self.$parent()?;
+                let span = expr.span.to(rhs.span);
                 let kind = ExprKind::BinaryOp {
                     op: operator,
                     lhs: Box::new(expr),
                     rhs: Box::new(rhs),
                 };
 
-                expr = Expr::new(self.reserve_id(), self.line, kind, self.top.clone());
+                expr = Expr::new(self.reserve_id(), span, kind, self.top.clone());
             }
 
             Ok(expr)
@@ -138,9 +362,10 @@ impl<'a> AstParser<'a> {
     binary_expr!(logical_and     , range         , (TokenType::AmpAmp));
     binary_expr!(range           , equality      , (TokenType::DotDot));
     binary_expr!(equality        , comparison    , (TokenType::BangEq | TokenType::EqEq));
-    binary_expr!(comparison      , additive      , (TokenType::Lt     | TokenType::Gt    | TokenType::LtEq | TokenType::GtEq));
-    binary_expr!(additive        , multiplicative, (TokenType::Plus   | TokenType::Minus));
-    binary_expr!(multiplicative  , unary         , (TokenType::Star   | TokenType::Slash | TokenType::Perc));
+    binary_expr!(comparison      , bitwise_shift , (TokenType::Lt     | TokenType::Gt    | TokenType::LtEq | TokenType::GtEq));
+    binary_expr!(bitwise_shift   , additive      , (TokenType::LtLt   | TokenType::GtGt));
+    binary_expr!(additive        , multiplicative, (TokenType::Plus   | TokenType::PlusPlus | TokenType::Minus));
+    binary_expr!(multiplicative  , power         , (TokenType::Star   | TokenType::Slash | TokenType::Perc));
 }
 
 #[cfg(test)]
@@ -148,9 +373,9 @@ mod tests {
     use itertools::Itertools;
 
     use crate::lexer::Lexer;
-    use crate::parser::ast::{BinaryOp, Expr, ExprKind, Literal};
+    use crate::parser::ast::{BinaryOp, Expr, ExprKind, Literal, MatchArm, Pattern};
+    use crate::parser::AstParser;
     use crate::symtable::SymbolTable;
-    use crate::AstParser;
 
     #[test]
     fn basic_expression() {
@@ -198,4 +423,155 @@ mod tests {
 
         assert_eq!(expected_ast, generated_ast);
     }
+
+    #[test]
+    fn power_precedence() {
+        let tokens = Lexer::new("1 << 2 ** 3 ** 2").collect_vec();
+
+        let expected_ast = Ok(Expr::without_table(6, ExprKind::BinaryOp {
+            op: BinaryOp::Shl,
+            lhs: Box::new(Expr::without_table(
+                0,
+                ExprKind::Literal(Literal::Integer(1)),
+            )),
+            rhs: Box::new(Expr::without_table(5, ExprKind::BinaryOp {
+                op: BinaryOp::Pow,
+                lhs: Box::new(Expr::without_table(
+                    1,
+                    ExprKind::Literal(Literal::Integer(2)),
+                )),
+                rhs: Box::new(Expr::without_table(4, ExprKind::BinaryOp {
+                    op: BinaryOp::Pow,
+                    lhs: Box::new(Expr::without_table(
+                        2,
+                        ExprKind::Literal(Literal::Integer(3)),
+                    )),
+                    rhs: Box::new(Expr::without_table(
+                        3,
+                        ExprKind::Literal(Literal::Integer(2)),
+                    )),
+                })),
+            })),
+        }));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.expression();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn expression_spans() {
+        let tokens = Lexer::new("foo(1,\n  2 * 3)").collect_vec();
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.expression().unwrap();
+
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        let ExprKind::Call { callee, args } = &generated_ast.kind else {
+            panic!("Expected a call");
+        };
+
+        let span = generated_ast.span;
+        assert_eq!((span.start.index, span.end.index), (0, 15));
+        assert_eq!((span.end.row, span.end.col), (1, 8));
+
+        let span = callee.span;
+        assert_eq!((span.start.index, span.end.index), (0, 3));
+
+        let span = args[1].span;
+        assert_eq!((span.start.index, span.end.index), (9, 14));
+        assert_eq!((span.start.row, span.start.col), (1, 2));
+    }
+
+    #[test]
+    fn struct_literal_fields() {
+        let tokens = Lexer::new("Point { x: 1, y: 2 }.x").collect_vec();
+
+        let expected_ast = Ok(Expr::without_table(3, ExprKind::FieldAccess {
+            value: Box::new(Expr::without_table(2, ExprKind::StructLiteral {
+                identifier: "Point".to_owned(),
+                fields: vec![
+                    (
+                        "x".to_owned(),
+                        Expr::without_table(0, Literal::Integer(1).into()),
+                    ),
+                    (
+                        "y".to_owned(),
+                        Expr::without_table(1, Literal::Integer(2).into()),
+                    ),
+                ],
+            })),
+            field: "x".to_owned(),
+        }));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.expression();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn match_variants() {
+        let tokens =
+            Lexer::new("match shape { Shape::Square(side) => side, _ => 0 }").collect_vec();
+
+        let expected_ast = Ok(Expr::without_table(3, ExprKind::Match {
+            value: Box::new(Expr::without_table(
+                0,
+                ExprKind::Identifier("shape".to_owned()),
+            )),
+            arms: vec![
+                MatchArm {
+                    pattern: Pattern::Variant {
+                        enumeration: "Shape".to_owned(),
+                        variant: "Square".to_owned(),
+                        bindings: vec!["side".to_owned()],
+                    },
+                    pattern_span: Default::default(),
+                    body: Expr::without_table(1, ExprKind::Identifier("side".to_owned())),
+                },
+                MatchArm {
+                    pattern: Pattern::Wildcard,
+                    pattern_span: Default::default(),
+                    body: Expr::without_table(2, Literal::Integer(0).into()),
+                },
+            ],
+        }));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.expression();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+    #[test]
+    fn repeated_array() {
+        let tokens = Lexer::new("[0; 10]").collect_vec();
+
+        let expected_ast = Ok(Expr::without_table(
+            2,
+            ExprKind::Literal(Literal::Repeat(
+                Box::new(Expr::without_table(0, Literal::Integer(0).into())),
+                Box::new(Expr::without_table(1, Literal::Integer(10).into())),
+            )),
+        ));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.expression();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
 }
@@ -1,6 +1,8 @@
 use std::fmt::{Error, Write};
 
-use super::ast::{Expr, ExprKind, Function, FunctionKind, Stmt, StmtKind};
+use super::ast::{
+    Enum, Expr, ExprKind, Function, FunctionKind, Stmt, StmtKind, Struct, TypeIdentifier,
+};
 
 pub struct GraphBuilder {
     graph: String,
@@ -63,28 +65,60 @@ impl GraphBuilder {
                     self.traverse_stmt0(else_then)?;
                 }
             }
-            StmtKind::WhileStmt { condition, body } => {
+            StmtKind::WhileStmt {
+                label,
+                condition,
+                body,
+            } => {
                 writeln!(
                     &mut self.graph,
-                    "N{} [shape=box label=\"WhileStmt\"];",
-                    stmt.id
+                    "N{} [shape=box label=\"WhileStmt{}\"];",
+                    stmt.id,
+                    label_text(label)
                 )?;
                 self.traverse_expr0(condition)?;
                 self.traverse_stmt0(body)?;
             }
             StmtKind::ForStmt {
+                label,
                 iterator,
-                identifier: _,
                 body,
+                ..
             } => {
                 writeln!(
                     &mut self.graph,
-                    "N{} [shape=box label=\"ForStmt\"];",
-                    stmt.id
+                    "N{} [shape=box label=\"ForStmt{}\"];",
+                    stmt.id,
+                    label_text(label)
                 )?;
                 self.traverse_expr0(iterator)?;
                 self.traverse_stmt0(body)?;
             }
+            StmtKind::Loop { label, body } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box label=\"Loop{}\"];",
+                    stmt.id,
+                    label_text(label)
+                )?;
+                self.traverse_stmt0(body)?;
+            }
+            StmtKind::Break(label) => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box label=\"Break{}\"];",
+                    stmt.id,
+                    label_text(label)
+                )?;
+            }
+            StmtKind::Continue(label) => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box label=\"Continue{}\"];",
+                    stmt.id,
+                    label_text(label)
+                )?;
+            }
             StmtKind::DefineValue {
                 identifier,
                 value,
@@ -95,7 +129,19 @@ impl GraphBuilder {
                     "N{} [shape=box label=\"DefineValue\\n\\nIdentifier={}\\lType={}\\l\"];",
                     stmt.id,
                     identifier,
-                    typ.clone().unwrap()
+                    type_text(typ)
+                )?;
+                self.traverse_expr0(value)?;
+            }
+            StmtKind::DefineConst {
+                identifier,
+                value,
+                typ,
+            } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box label=\"DefineConst\\n\\nIdentifier={}\\lType={}\\l\"];",
+                    stmt.id, identifier, typ
                 )?;
                 self.traverse_expr0(value)?;
             }
@@ -109,20 +155,25 @@ impl GraphBuilder {
                     "N{} [shape=box label=\"DefineVariable\\n\\nIdentifier={}\\lType={}\\l\"];",
                     stmt.id,
                     identifier,
-                    typ.clone().unwrap()
+                    type_text(typ)
                 )?;
                 self.traverse_expr0(value)?;
             }
-            StmtKind::AssignVariable { identifier, value } => {
+            StmtKind::Assign { target, op, value } => {
+                let op = op
+                    .map(|it| format!("{it}="))
+                    .unwrap_or_else(|| "=".to_owned());
                 writeln!(
                     &mut self.graph,
-                    "N{} [shape=box label=\"AssignVariable\\n\\nIdentifier={}\"];",
-                    stmt.id, identifier
+                    "N{} [shape=box label=\"Assign\\n\\nOp={}\\l\"];",
+                    stmt.id, op
                 )?;
+                self.traverse_expr0(target)?;
                 self.traverse_expr0(value)?;
             }
             StmtKind::DefineFunction(Function {
                 identifier,
+                params,
                 inputs,
                 output,
                 kind,
@@ -130,10 +181,11 @@ impl GraphBuilder {
                 writeln!(
                     &mut self.graph,
                     "N{} [shape=box \
-                     label=\"DefineFunction\\n\\nIdentifier={}\\lInputs={}\\lOutput={}\\lKind={}\\\
-                     l\"];",
+                     label=\"DefineFunction\\n\\nIdentifier={}\\lParams={}\\lInputs={}\\\
+                     lOutput={}\\lKind={}\\l\"];",
                     stmt.id,
                     identifier,
+                    params.join(", "),
                     inputs.len(),
                     output.is_some(),
                     match kind {
@@ -146,13 +198,57 @@ impl GraphBuilder {
                     self.traverse_stmt0(body)?;
                 }
             }
+            StmtKind::DefineStruct(Struct {
+                identifier,
+                params,
+                fields,
+            }) => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box \
+                     label=\"DefineStruct\\n\\nIdentifier={}\\lParams={}\\lFields={}\\l\"];",
+                    stmt.id,
+                    identifier,
+                    params.join(", "),
+                    fields.len()
+                )?;
+            }
+            StmtKind::DefineEnum(Enum {
+                identifier,
+                variants,
+            }) => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box label=\"DefineEnum\\n\\nIdentifier={}\\lVariants={}\\l\"];",
+                    stmt.id,
+                    identifier,
+                    variants.len()
+                )?;
+            }
+            StmtKind::Match { value, arms } => {
+                writeln!(&mut self.graph, "N{} [shape=box label=\"Match\"];", stmt.id)?;
+                self.traverse_expr0(value)?;
+                for arm in arms {
+                    self.traverse_stmt0(&arm.body)?;
+                }
+            }
             StmtKind::Return(expr) => {
                 writeln!(
                     &mut self.graph,
                     "N{} [shape=box label=\"Return\"];",
                     stmt.id
                 )?;
-                self.traverse_expr0(expr)?;
+                if let Some(expr) = expr {
+                    self.traverse_expr0(expr)?;
+                }
+            }
+            StmtKind::Use(path) => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box label=\"Use\\n\\nPath={}\"];",
+                    stmt.id,
+                    path.join("::")
+                )?;
             }
         }
 
@@ -211,6 +307,58 @@ impl GraphBuilder {
                     self.traverse_expr0(arg)?;
                 }
             }
+            ExprKind::StructLiteral { identifier, fields } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box style=rounded label=\"{} {{}}\"];",
+                    expr.id, identifier
+                )?;
+                for (_, value) in fields {
+                    self.traverse_expr0(value)?;
+                }
+            }
+            ExprKind::FieldAccess { value, field } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box style=rounded label=\".{}\"];",
+                    expr.id, field
+                )?;
+                self.traverse_expr0(value)?;
+            }
+            ExprKind::Index { value, index } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box style=rounded label=\"[]\"];",
+                    expr.id
+                )?;
+                self.traverse_expr0(value)?;
+                self.traverse_expr0(index)?;
+            }
+            ExprKind::Variant {
+                enumeration,
+                variant,
+                args,
+            } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box style=rounded label=\"{}::{}\"];",
+                    expr.id, enumeration, variant
+                )?;
+                for arg in args {
+                    self.traverse_expr0(arg)?;
+                }
+            }
+            ExprKind::Match { value, arms } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} [shape=box style=rounded label=\"Match\"];",
+                    expr.id
+                )?;
+                self.traverse_expr0(value)?;
+                for arm in arms {
+                    self.traverse_expr0(&arm.body)?;
+                }
+            }
         }
 
         Ok(())
@@ -255,7 +403,9 @@ impl GraphBuilder {
                     self.traverse_stmt(else_then)?;
                 }
             }
-            StmtKind::WhileStmt { condition, body } => {
+            StmtKind::WhileStmt {
+                condition, body, ..
+            } => {
                 writeln!(
                     &mut self.graph,
                     "N{} -> N{} [label = \"Condition\"];",
@@ -269,11 +419,7 @@ impl GraphBuilder {
                 self.traverse_expr(condition)?;
                 self.traverse_stmt(body)?;
             }
-            StmtKind::ForStmt {
-                iterator,
-                identifier: _,
-                body,
-            } => {
+            StmtKind::ForStmt { iterator, body, .. } => {
                 writeln!(
                     &mut self.graph,
                     "N{} -> N{} [label = \"Iterator\"];",
@@ -287,16 +433,34 @@ impl GraphBuilder {
                 self.traverse_expr(iterator)?;
                 self.traverse_stmt(body)?;
             }
+            StmtKind::Loop { body, .. } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} -> N{} [label = \"Body\"];",
+                    stmt.id, body.id
+                )?;
+                self.traverse_stmt(body)?;
+            }
             StmtKind::DefineVariable { value, .. } => {
                 writeln!(&mut self.graph, "N{} -> N{};", stmt.id, value.id)?;
                 self.traverse_expr(value)?;
             }
-            StmtKind::DefineValue { value, .. } => {
+            StmtKind::DefineValue { value, .. } | StmtKind::DefineConst { value, .. } => {
                 writeln!(&mut self.graph, "N{} -> N{};", stmt.id, value.id)?;
                 self.traverse_expr(value)?;
             }
-            StmtKind::AssignVariable { value, .. } => {
-                writeln!(&mut self.graph, "N{} -> N{};", stmt.id, value.id)?;
+            StmtKind::Assign { target, value, .. } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} -> N{} [label = \"Target\"];",
+                    stmt.id, target.id
+                )?;
+                writeln!(
+                    &mut self.graph,
+                    "N{} -> N{} [label = \"Value\"];",
+                    stmt.id, value.id
+                )?;
+                self.traverse_expr(target)?;
                 self.traverse_expr(value)?;
             }
             StmtKind::DefineFunction(Function { kind, .. }) => {
@@ -310,10 +474,32 @@ impl GraphBuilder {
                     self.traverse_stmt(body)?;
                 }
             }
-            StmtKind::Return(value) => {
+            StmtKind::Return(Some(value)) => {
                 writeln!(&mut self.graph, "N{} -> N{};", stmt.id, value.id)?;
                 self.traverse_expr(value)?;
             }
+            StmtKind::Return(None) => (),
+            StmtKind::Match { value, arms } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} -> N{} [label = \"Value\"];",
+                    stmt.id, value.id
+                )?;
+                self.traverse_expr(value)?;
+                for arm in arms {
+                    writeln!(
+                        &mut self.graph,
+                        "N{} -> N{} [label = \"{}\"];",
+                        stmt.id, arm.body.id, arm.pattern
+                    )?;
+                    self.traverse_stmt(&arm.body)?;
+                }
+            }
+            StmtKind::Break(_)
+            | StmtKind::Continue(_)
+            | StmtKind::DefineStruct(_)
+            | StmtKind::DefineEnum(_)
+            | StmtKind::Use(_) => (),
         }
 
         Ok(())
@@ -347,9 +533,71 @@ impl GraphBuilder {
                     self.traverse_expr(arg)?;
                 }
             }
+            ExprKind::StructLiteral { fields, .. } => {
+                for (field, value) in fields {
+                    writeln!(
+                        &mut self.graph,
+                        "N{} -> N{} [label=\"{}\"];",
+                        expr.id, value.id, field
+                    )?;
+                    self.traverse_expr(value)?;
+                }
+            }
+            ExprKind::FieldAccess { value, .. } => {
+                writeln!(&mut self.graph, "N{} -> N{};", expr.id, value.id)?;
+                self.traverse_expr(value)?;
+            }
+            ExprKind::Index { value, index } => {
+                writeln!(&mut self.graph, "N{} -> N{};", expr.id, value.id)?;
+                writeln!(
+                    &mut self.graph,
+                    "N{} -> N{} [label=index];",
+                    expr.id, index.id
+                )?;
+                self.traverse_expr(value)?;
+                self.traverse_expr(index)?;
+            }
+            ExprKind::Variant { args, .. } => {
+                for arg in args {
+                    writeln!(&mut self.graph, "N{} -> N{} [label=arg];", expr.id, arg.id)?;
+                    self.traverse_expr(arg)?;
+                }
+            }
+            ExprKind::Match { value, arms } => {
+                writeln!(
+                    &mut self.graph,
+                    "N{} -> N{} [label=value];",
+                    expr.id, value.id
+                )?;
+                self.traverse_expr(value)?;
+                for arm in arms {
+                    writeln!(
+                        &mut self.graph,
+                        "N{} -> N{} [label=\"{}\"];",
+                        expr.id, arm.body.id, arm.pattern
+                    )?;
+                    self.traverse_expr(&arm.body)?;
+                }
+            }
             _ => (),
         }
 
         Ok(())
     }
 }
+
+/// Label of a loop shown in its node, empty for loops without a label.
+fn label_text(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("\\n\\nLabel={label}"),
+        None => String::new(),
+    }
+}
+
+/// Type written in a declaration, `_` if it's inferred from the value.
+fn type_text(typ: &Option<TypeIdentifier>) -> String {
+    match typ {
+        Some(typ) => typ.to_string(),
+        None => "_".to_owned(),
+    }
+}
@@ -4,22 +4,44 @@ pub mod graph;
 pub mod stmt;
 
 use self::ast::{Literal, Stmt, StmtKind, TypeIdentifier};
-use crate::lexer::{Token, TokenType};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::{Span, Token, TokenType};
 use crate::symtable::SymbolTable;
 
 #[derive(thiserror::Error, Debug, PartialEq)]
 pub enum ParsingError {
     #[error("Invalid operation")]
-    InvalidOp,
+    InvalidOp(Span),
     #[error("Unexpected token '{1}'. {2}")]
-    UnexpectedToken(u32, TokenType, &'static str),
+    UnexpectedToken(Span, TokenType, &'static str),
+    #[error("Invalid left-hand side of assignment")]
+    InvalidAssignment(Span),
 }
 
 impl ParsingError {
-    pub fn line(&self) -> u32 {
+    pub fn span(&self) -> Span {
         match &self {
-            ParsingError::InvalidOp => 0,
-            ParsingError::UnexpectedToken(x, _, _) => *x,
+            ParsingError::InvalidOp(span) => *span,
+            ParsingError::UnexpectedToken(span, _, _) => *span,
+            ParsingError::InvalidAssignment(span) => *span,
+        }
+    }
+}
+
+impl From<&ParsingError> for Diagnostic {
+    fn from(value: &ParsingError) -> Self {
+        match value {
+            ParsingError::InvalidOp(span) => {
+                Diagnostic::error(value.to_string()).with_primary(*span, "")
+            }
+            ParsingError::UnexpectedToken(span, tt, message) => {
+                Diagnostic::error(format!("Unexpected token '{tt}'")).with_primary(*span, *message)
+            }
+            ParsingError::InvalidAssignment(span) => Diagnostic::error(value.to_string())
+                .with_primary(
+                    *span,
+                    "only variables, fields, elements and pointers can be assigned to",
+                ),
         }
     }
 }
@@ -30,21 +52,50 @@ pub struct AstParser<'a> {
     tokens: Vec<Token<'a>>,
     index: usize,
     id: i32,
-    line: u32,
+    /// Span of the last consumed token
+    previous: Span,
+    /// Errors the parser recovered from
+    errors: Vec<ParsingError>,
+    /// Whether an identifier followed by `{` starts a struct literal. Turned
+    /// off in front of blocks so `if x { ... }` isn't parsed as a literal.
+    struct_literals: bool,
 }
 
 impl<'a> AstParser<'a> {
-    pub fn parse(tokens: Vec<Token<'a>>, root: SymbolTable) -> Result<Stmt, ParsingError> {
+    pub fn parse(tokens: Vec<Token<'a>>, root: SymbolTable) -> Result<Stmt, Vec<ParsingError>> {
         let mut parser = Self::new(tokens, root);
+        let start = parser.peek_span();
 
+        // Imports and constants are only allowed at the top level of a module, which
+        // is why they are handled here instead of in `statement`.
         let mut statements = Vec::new();
         while !parser.eof() {
-            statements.push(parser.statement()?);
+            let stmt = match parser.peek().tt {
+                TokenType::Use => parser.use_stmt(),
+                TokenType::Const => parser.define_const(),
+                _ => parser.statement(),
+            };
+
+            match stmt {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    parser.errors.push(error);
+                    parser.synchronize();
+
+                    // Recovery stops in front of a closing brace, at the top level there
+                    // is no block it could belong to so it is skipped.
+                    parser.advance_if_eq(&TokenType::ClosingBrace);
+                }
+            }
+        }
+
+        if !parser.errors.is_empty() {
+            return Err(parser.errors);
         }
 
         let root = Stmt::new(
             parser.reserve_id(),
-            parser.line,
+            parser.span_from(start),
             StmtKind::Block(statements),
             parser.top.clone(),
         );
@@ -55,13 +106,34 @@ impl<'a> AstParser<'a> {
 
 /// Implementation containing utilities used by the parsers internal components
 impl<'a> AstParser<'a> {
-    pub fn new(tokens: Vec<Token<'a>>, root: SymbolTable) -> Self {
+    pub fn new(mut tokens: Vec<Token<'a>>, root: SymbolTable) -> Self {
+        let previous = tokens
+            .first()
+            .map(|it| it.span().shrink_to_start())
+            .unwrap_or_default();
+
+        // Ending the tokens with an end of file token means there is always a
+        // token to peek at
+        let end = tokens
+            .last()
+            .map(|it| it.span().shrink_to_end())
+            .unwrap_or_default();
+        tokens.push(Token {
+            tt: TokenType::Eof,
+            lexeme: "",
+            start: end.start,
+            end: end.end,
+            file: end.file,
+        });
+
         Self {
             top: root,
             tokens,
             index: 0,
             id: 0,
-            line: 0,
+            previous,
+            errors: Vec::new(),
+            struct_literals: true,
         }
     }
 
@@ -70,7 +142,8 @@ impl<'a> AstParser<'a> {
     }
 
     pub fn peek2(&self) -> &Token {
-        &self.tokens[self.index + 1]
+        let index = (self.index + 1).min(self.tokens.len() - 1);
+        &self.tokens[index]
     }
 
     pub fn advance(&mut self) -> Option<&Token> {
@@ -80,10 +153,21 @@ impl<'a> AstParser<'a> {
 
         let current = &self.tokens[self.index];
         self.index += 1;
-        self.line = current.start.row;
+        self.previous = current.span();
         Some(current)
     }
 
+    /// Span of the next token. At the end of the input this is an empty span
+    /// right after the last token.
+    pub fn peek_span(&self) -> Span {
+        self.peek().span()
+    }
+
+    /// Span from the start of `start` up to the end of the last consumed token.
+    pub fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous)
+    }
+
     pub fn advance_if(&mut self, next: impl FnOnce(&Token) -> bool) -> bool {
         if self.eof() {
             return false;
@@ -106,30 +190,40 @@ impl<'a> AstParser<'a> {
         next: TokenType,
         error: &'static str,
     ) -> Result<&Token, ParsingError> {
-        if std::mem::discriminant(&self.peek().tt) != std::mem::discriminant(&next) {
-            return Err(ParsingError::UnexpectedToken(self.line, next, error));
+        let token = self.peek();
+        if std::mem::discriminant(&token.tt) != std::mem::discriminant(&next) {
+            return Err(ParsingError::UnexpectedToken(
+                token.span(),
+                token.tt.clone(),
+                error,
+            ));
         }
 
         Ok(self.advance().unwrap())
     }
 
     pub fn consume_literal(&mut self) -> Result<Literal, ParsingError> {
-        let Some(TokenType::Literal(literal)) = self.advance().map(|it| it.tt.clone()) else {
-            return Err(ParsingError::UnexpectedToken(self.line, self.peek().tt.clone(), "Expected literal"));
+        let token = self.peek();
+        let TokenType::Literal(literal) = token.tt.clone() else {
+            return Err(ParsingError::UnexpectedToken(token.span(), token.tt.clone(), "Expected literal"));
         };
 
+        self.advance();
         Ok(literal.into())
     }
 
     pub fn consume_identifier(&mut self) -> Result<String, ParsingError> {
-        let Some(TokenType::Identifier(identifier)) = self.advance().map(|it| it.tt.clone()) else {
-            return Err(ParsingError::UnexpectedToken(self.line, self.peek().tt.clone(), "Expected identifier"));
+        let token = self.peek();
+        let TokenType::Identifier(identifier) = token.tt.clone() else {
+            return Err(ParsingError::UnexpectedToken(token.span(), token.tt.clone(), "Expected identifier"));
         };
 
+        self.advance();
         Ok(identifier)
     }
 
     pub fn consume_type(&mut self) -> Result<TypeIdentifier, ParsingError> {
+        let is_pointer = self.advance_if_eq(&TokenType::Star);
         let is_list = self.peek().tt == TokenType::OpeningBracket;
 
         if is_list {
@@ -138,11 +232,65 @@ impl<'a> AstParser<'a> {
 
         let name = self.consume_identifier()?;
 
+        let mut args = Vec::new();
+        if self.advance_if_eq(&TokenType::Lt) {
+            loop {
+                args.push(self.consume_type()?);
+
+                if !self.advance_if_eq(&TokenType::Comma) {
+                    break;
+                }
+            }
+
+            self.consume_closing_angle()?;
+        }
+
         if is_list {
             self.consume(TokenType::ClosingBracket, "Expected ']'")?;
         }
 
-        Ok(TypeIdentifier { name, is_list })
+        Ok(TypeIdentifier {
+            name,
+            args,
+            is_list,
+            is_pointer,
+        })
+    }
+
+    /// Type parameters of a definition such as `<T, U>`, empty if the
+    /// definition isn't generic.
+    pub fn type_params(&mut self) -> Result<Vec<String>, ParsingError> {
+        let mut params = Vec::new();
+        if self.advance_if_eq(&TokenType::Lt) {
+            loop {
+                params.push(self.consume_identifier()?);
+
+                if !self.advance_if_eq(&TokenType::Comma) {
+                    break;
+                }
+            }
+
+            self.consume_closing_angle()?;
+        }
+
+        Ok(params)
+    }
+
+    /// Consume the `>` closing a list of type arguments. Nested lists end in
+    /// `>>` which is lexed as a single token, only its first half is consumed.
+    fn consume_closing_angle(&mut self) -> Result<(), ParsingError> {
+        let token = &mut self.tokens[self.index];
+        if token.tt == TokenType::GtGt {
+            token.tt = TokenType::Gt;
+            token.lexeme = &token.lexeme[1..];
+            token.start.index += 1;
+            token.start.col += 1;
+
+            return Ok(());
+        }
+
+        self.consume(TokenType::Gt, "Expected '>'")?;
+        Ok(())
     }
 
     pub fn reserve_id(&mut self) -> i32 {
@@ -160,6 +308,31 @@ impl<'a> AstParser<'a> {
     }
 
     pub fn eof(&self) -> bool {
-        self.index >= self.tokens.len()
+        self.peek().tt == TokenType::Eof
+    }
+
+    /// Skip tokens until the end of the current statement so parsing can
+    /// continue after an error. Stops after a `;`, after a block belonging to
+    /// the statement or in front of a `}` closing the enclosing block.
+    pub fn synchronize(&mut self) {
+        let mut depth = 0;
+        while !self.eof() {
+            match self.peek().tt {
+                TokenType::SemiColon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::ClosingBrace if depth == 0 => return,
+                TokenType::ClosingBrace if depth == 1 => {
+                    self.advance();
+                    return;
+                }
+                TokenType::OpeningBrace => depth += 1,
+                TokenType::ClosingBrace => depth -= 1,
+                _ => (),
+            }
+
+            self.advance();
+        }
     }
 }
@@ -1,4 +1,7 @@
-use super::ast::{Function, FunctionInput, FunctionKind, Stmt, StmtKind};
+use super::ast::{
+    BinaryOp, Enum, EnumVariant, Function, FunctionInput, FunctionKind, MatchArm, Stmt, StmtKind,
+    Struct, StructField,
+};
 use super::{AstParser, ParsingError};
 use crate::lexer::TokenType;
 
@@ -13,14 +16,20 @@ impl<'a> AstParser<'a> {
             TokenType::Foreign => self.foreign(),
 
             TokenType::If => self.if_stmt(),
-            TokenType::While => self.while_stmt(),
-            TokenType::For => self.for_stmt(),
+            TokenType::While => self.while_stmt(None),
+            TokenType::For => self.for_stmt(None),
+            TokenType::Loop => self.loop_stmt(None),
+            TokenType::Break => self.break_stmt(),
+            TokenType::Continue => self.continue_stmt(),
             TokenType::Var => self.define_variable(),
             TokenType::Val => self.define_value(),
             TokenType::Fn => self.define_function(false),
+            TokenType::Struct => self.define_struct(),
+            TokenType::Enum => self.define_enum(),
+            TokenType::Match => self.match_stmt(),
             TokenType::Return => self.return_stmt(),
 
-            _ if self.peek2().tt == TokenType::Eq => self.assign_variable(),
+            TokenType::Identifier(_) if self.peek2().tt == TokenType::Colon => self.labeled_loop(),
             _ => self.expression_stmt(),
         }
     }
@@ -33,19 +42,22 @@ impl<'a> AstParser<'a> {
         // Sloth sees a foreign keyword it expects something to follow
         // determining what from the other language you want to get, this is
         // similar to the "statement" function but more trimmed down.
-        match &self.peek().tt {
+        let token = self.peek();
+        match &token.tt {
             TokenType::Fn => self.define_function(true),
 
-            tt => Err(ParsingError::UnexpectedToken(self.line, tt.clone(), "")),
+            tt => Err(ParsingError::UnexpectedToken(token.span(), tt.clone(), "")),
         }
     }
 
     fn if_stmt(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         // Consume the if token
         self.consume(TokenType::If, "Expected if")?;
 
         // Get the condition and if_then of the if statement
-        let condition = self.expression()?;
+        let condition = self.condition()?;
         let if_then = self.block()?;
 
         // Check if there is an else
@@ -67,43 +79,73 @@ impl<'a> AstParser<'a> {
 
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
 
-    fn while_stmt(&mut self) -> Result<Stmt, ParsingError> {
+    fn labeled_loop(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        let label = Some(self.consume_identifier()?);
+        self.consume(TokenType::Colon, "Expected ':'")?;
+
+        let mut stmt = match &self.peek().tt {
+            TokenType::While => self.while_stmt(label)?,
+            TokenType::For => self.for_stmt(label)?,
+            TokenType::Loop => self.loop_stmt(label)?,
+            tt => {
+                return Err(ParsingError::UnexpectedToken(
+                    self.peek_span(),
+                    tt.clone(),
+                    "Expected a loop after the label",
+                ))
+            }
+        };
+
+        // The label is part of the loop
+        stmt.span = self.span_from(start);
+        Ok(stmt)
+    }
+
+    fn while_stmt(&mut self, label: Option<String>) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         // Consume the while token
         self.consume(TokenType::While, "Expected while")?;
 
-        let condition = self.expression()?;
+        let condition = self.condition()?;
         let body = self.block()?;
 
         let kind = StmtKind::WhileStmt {
+            label,
             condition,
             body: body.into(),
         };
 
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
 
-    fn for_stmt(&mut self) -> Result<Stmt, ParsingError> {
+    fn for_stmt(&mut self, label: Option<String>) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         // Consume the for token
         self.consume(TokenType::For, "Expected for")?;
 
         let identifier = self.consume_identifier()?;
         self.consume(TokenType::In, "Expected in")?;
-        let iterator = self.expression()?;
+        let iterator = self.condition()?;
 
         let body = self.block()?;
 
         let kind = StmtKind::ForStmt {
+            label,
             iterator,
             identifier,
             body: body.into(),
@@ -111,21 +153,81 @@ impl<'a> AstParser<'a> {
 
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
+            kind,
+            self.top.clone(),
+        ))
+    }
+
+    fn loop_stmt(&mut self, label: Option<String>) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        // Consume the loop token
+        self.consume(TokenType::Loop, "Expected loop")?;
+
+        let body = self.block()?;
+        let kind = StmtKind::Loop {
+            label,
+            body: body.into(),
+        };
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
 
-    // TODO: Make variable types optional
+    fn break_stmt(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        self.consume(TokenType::Break, "Expected break")?;
+        let label = self.loop_label()?;
+        self.consume(TokenType::SemiColon, "Expected ';' at end of statement")?;
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
+            StmtKind::Break(label),
+            self.top.clone(),
+        ))
+    }
+
+    fn continue_stmt(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        self.consume(TokenType::Continue, "Expected continue")?;
+        let label = self.loop_label()?;
+        self.consume(TokenType::SemiColon, "Expected ';' at end of statement")?;
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
+            StmtKind::Continue(label),
+            self.top.clone(),
+        ))
+    }
+
+    /// Label of the loop a break or continue refers to, None for the
+    /// innermost loop.
+    fn loop_label(&mut self) -> Result<Option<String>, ParsingError> {
+        match self.peek().tt {
+            TokenType::Identifier(_) => Ok(Some(self.consume_identifier()?)),
+            _ => Ok(None),
+        }
+    }
+
     fn define_variable(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         // Consume the var token
         self.consume(TokenType::Var, "Expected var")?;
 
         // Get the identifier and type
         let identifier = self.consume_identifier()?;
         let typ = if self.consume(TokenType::Colon, "Expected ':'").is_ok() {
-            self.consume_type().ok()
+            Some(self.consume_type()?)
         } else {
             None
         };
@@ -144,20 +246,22 @@ impl<'a> AstParser<'a> {
 
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
 
     fn define_value(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         // Consume the val token
         self.consume(TokenType::Val, "Expected val")?;
 
         // Get the identifier and type
         let identifier = self.consume_identifier()?;
         let typ = if self.consume(TokenType::Colon, "Expected ':'").is_ok() {
-            self.consume_type().ok()
+            Some(self.consume_type()?)
         } else {
             None
         };
@@ -176,17 +280,51 @@ impl<'a> AstParser<'a> {
 
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
+
+    pub(super) fn define_const(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        // Consume the const token
+        self.consume(TokenType::Const, "Expected const")?;
+
+        // Constants always have a type
+        let identifier = self.consume_identifier()?;
+        self.consume(TokenType::Colon, "Expected ':'")?;
+        let typ = self.consume_type()?;
+
+        self.consume(TokenType::Eq, "Expected '='")?;
+        let value = self.expression()?;
+
+        self.consume(TokenType::SemiColon, "Expected ';' at end of statement")?;
+
+        let kind = StmtKind::DefineConst {
+            identifier,
+            value,
+            typ,
+        };
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
+            kind,
+            self.top.clone(),
+        ))
+    }
+
     // TODO: Make argument types optional
     fn define_function(&mut self, is_foreign: bool) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         // Consume the fn token
         self.consume(TokenType::Fn, "Expected fn")?;
 
         let identifier = self.consume_identifier()?;
+        let params = self.type_params()?;
 
         // Get the function inputs
         self.consume(TokenType::OpeningParen, "Expected '('")?;
@@ -233,6 +371,7 @@ impl<'a> AstParser<'a> {
 
         let stmt = StmtKind::DefineFunction(Function {
             identifier,
+            params,
             inputs,
             output,
             kind,
@@ -240,46 +379,239 @@ impl<'a> AstParser<'a> {
 
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             stmt,
             self.top.clone(),
         ))
     }
 
+    fn define_struct(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        // Consume the struct token
+        self.consume(TokenType::Struct, "Expected struct")?;
+
+        let identifier = self.consume_identifier()?;
+        let params = self.type_params()?;
+
+        // Get the fields, separated by commas with an optional trailing comma
+        self.consume(TokenType::OpeningBrace, "Expected '{'")?;
+
+        let mut fields = Vec::new();
+        while matches!(self.peek().tt, TokenType::Identifier(_)) {
+            let field_identifier = self.consume_identifier()?;
+            self.consume(TokenType::Colon, "Expected ':'")?;
+            let field_type = self.consume_type()?;
+
+            fields.push(StructField {
+                identifier: field_identifier,
+                typ: field_type,
+            });
+
+            if !self.advance_if_eq(&TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::ClosingBrace, "Expected '}'")?;
+
+        let kind = StmtKind::DefineStruct(Struct {
+            identifier,
+            params,
+            fields,
+        });
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
+            kind,
+            self.top.clone(),
+        ))
+    }
+
+    fn define_enum(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        // Consume the enum token
+        self.consume(TokenType::Enum, "Expected enum")?;
+
+        let identifier = self.consume_identifier()?;
+
+        // Get the variants, each with an optional list of field types
+        self.consume(TokenType::OpeningBrace, "Expected '{'")?;
+
+        let mut variants = Vec::new();
+        while matches!(self.peek().tt, TokenType::Identifier(_)) {
+            let variant_identifier = self.consume_identifier()?;
+
+            let mut fields = Vec::new();
+            if self.advance_if_eq(&TokenType::OpeningParen) {
+                while !self.eof() && self.peek().tt != TokenType::ClosingParen {
+                    fields.push(self.consume_type()?);
+                    if !self.advance_if_eq(&TokenType::Comma) {
+                        break;
+                    }
+                }
+
+                self.consume(TokenType::ClosingParen, "Expected ')'")?;
+            }
+
+            variants.push(EnumVariant {
+                identifier: variant_identifier,
+                fields,
+            });
+
+            if !self.advance_if_eq(&TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::ClosingBrace, "Expected '}'")?;
+
+        let kind = StmtKind::DefineEnum(Enum {
+            identifier,
+            variants,
+        });
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
+            kind,
+            self.top.clone(),
+        ))
+    }
+
+    fn match_stmt(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        // Consume the match token
+        self.consume(TokenType::Match, "Expected match")?;
+
+        let value = self.condition()?;
+        self.consume(TokenType::OpeningBrace, "Expected '{'")?;
+
+        let mut arms = Vec::new();
+        while !self.eof() && self.peek().tt != TokenType::ClosingBrace {
+            let pattern_start = self.peek_span();
+            let pattern = self.pattern()?;
+            let pattern_span = self.span_from(pattern_start);
+            self.consume(TokenType::FatArrow, "Expected '=>'")?;
+
+            // Every arm gets its own scope for the symbols bound by its pattern
+            self.push_table();
+            let body = self.match_arm_body();
+            self.pop_table();
+            let body = body?;
+
+            // Arms with a block as their body don't have to be followed by a comma
+            let is_block = matches!(body.kind, StmtKind::Block(_));
+            arms.push(MatchArm {
+                pattern,
+                pattern_span,
+                body,
+            });
+
+            if !self.advance_if_eq(&TokenType::Comma) && !is_block {
+                break;
+            }
+        }
+
+        self.consume(TokenType::ClosingBrace, "Expected '}'")?;
+
+        let kind = StmtKind::Match { value, arms };
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
+            kind,
+            self.top.clone(),
+        ))
+    }
+
+    fn match_arm_body(&mut self) -> Result<Stmt, ParsingError> {
+        if self.peek().tt == TokenType::OpeningBrace {
+            return self.block();
+        }
+
+        let start = self.peek_span();
+        let expr = self.expression()?;
+        let kind = StmtKind::ExprStmt(expr);
+
+        Ok(Stmt::new(
+            self.reserve_id(),
+            self.span_from(start),
+            kind,
+            self.top.clone(),
+        ))
+    }
+
     fn return_stmt(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         self.consume(TokenType::Return, "Expected return")?;
-        let value = self.expression()?;
+        let value = match self.peek().tt {
+            TokenType::SemiColon => None,
+            _ => Some(self.expression()?),
+        };
         self.consume(TokenType::SemiColon, "Expected ';' at end of statement")?;
         let kind = StmtKind::Return(value);
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
 
-    fn assign_variable(&mut self) -> Result<Stmt, ParsingError> {
-        let identifier = self.consume_identifier()?;
-        self.consume(TokenType::Eq, "Expected '='")?;
-        let value = self.expression()?;
+    pub(super) fn use_stmt(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
+        self.consume(TokenType::Use, "Expected use")?;
+
+        // Get the path of the module, segments are separated by `::`
+        let mut path = vec![self.consume_identifier()?];
+        while self.advance_if_eq(&TokenType::ColonColon) {
+            path.push(self.consume_identifier()?);
+        }
+
         self.consume(TokenType::SemiColon, "Expected ';' at end of statement")?;
-        let kind = StmtKind::AssignVariable { identifier, value };
+
+        let kind = StmtKind::Use(path);
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
     }
 
     fn expression_stmt(&mut self) -> Result<Stmt, ParsingError> {
+        let start = self.peek_span();
+
         let expr = self.expression()?;
+
+        // Assignments start with the place that is assigned to, compound
+        // assignments apply their operator to its current value
+        let op = BinaryOp::compound(&self.peek().tt);
+        let kind = if op.is_some() || self.peek().tt == TokenType::Eq {
+            self.advance();
+            if !expr.is_place() {
+                return Err(ParsingError::InvalidAssignment(expr.span));
+            }
+
+            StmtKind::Assign {
+                target: expr,
+                op,
+                value: self.expression()?,
+            }
+        } else {
+            StmtKind::ExprStmt(expr)
+        };
+
         self.consume(TokenType::SemiColon, "Expected ';' at end of statement")?;
-        let kind = StmtKind::ExprStmt(expr);
         Ok(Stmt::new(
             self.reserve_id(),
-            self.line,
+            self.span_from(start),
             kind,
             self.top.clone(),
         ))
@@ -289,13 +621,21 @@ impl<'a> AstParser<'a> {
         // This inner function exists to make cleanup of the pushed symbol table easier
         // in the case of a parsing error.
         fn inner(this: &mut AstParser) -> Result<Stmt, ParsingError> {
+            let start = this.peek_span();
+
             // Consume the opening brace
             this.consume(TokenType::OpeningBrace, "Expected '{'")?;
 
             // Get the body of the block
             let mut body = Vec::new();
             while !this.eof() && this.peek().tt != TokenType::ClosingBrace {
-                body.push(this.statement()?);
+                match this.statement() {
+                    Ok(stmt) => body.push(stmt),
+                    Err(error) => {
+                        this.errors.push(error);
+                        this.synchronize();
+                    }
+                }
             }
 
             // Consume the closing brace
@@ -305,7 +645,7 @@ impl<'a> AstParser<'a> {
 
             Ok(Stmt::new(
                 this.reserve_id(),
-                this.line,
+                this.span_from(start),
                 kind,
                 this.top.clone(),
             ))
@@ -324,11 +664,11 @@ impl<'a> AstParser<'a> {
 mod tests {
     use itertools::Itertools;
 
-    use super::{AstParser, StmtKind};
-    use crate::lexer::Lexer;
+    use super::{AstParser, ParsingError, StmtKind};
+    use crate::lexer::{Lexer, TokenType};
     use crate::parser::ast::{
-        BinaryOp, Expr, ExprKind, Function, FunctionInput, FunctionKind, Literal, Stmt,
-        TypeIdentifier,
+        BinaryOp, Enum, EnumVariant, Expr, ExprKind, Function, FunctionInput, FunctionKind,
+        Literal, Stmt, Struct, StructField, TypeIdentifier,
     };
     use crate::symtable::SymbolTable;
 
@@ -359,6 +699,110 @@ mod tests {
         assert_eq!(expected_ast, generated_ast);
     }
 
+    #[test]
+    fn return_without_value() {
+        let tokens = Lexer::new("{ return; }").collect_vec();
+
+        let expected_ast = Ok(Stmt::without_table(
+            1,
+            StmtKind::Block(vec![Stmt::without_table(0, StmtKind::Return(None))]),
+        ));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.statement();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn labeled_loop() {
+        let tokens = Lexer::new("outer: loop { continue; break outer; }").collect_vec();
+
+        let expected_ast = Ok(Stmt::without_table(3, StmtKind::Loop {
+            label: Some("outer".to_string()),
+            body: Box::new(Stmt::without_table(
+                2,
+                StmtKind::Block(vec![
+                    Stmt::without_table(0, StmtKind::Continue(None)),
+                    Stmt::without_table(1, StmtKind::Break(Some("outer".to_string()))),
+                ]),
+            )),
+        }));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.statement();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn compound_assignment() {
+        let tokens = Lexer::new("totals[i] **= 2;").collect_vec();
+
+        let expected_ast = Ok(Stmt::without_table(4, StmtKind::Assign {
+            target: Expr::without_table(2, ExprKind::Index {
+                value: Box::new(Expr::without_table(
+                    0,
+                    ExprKind::Identifier("totals".to_string()),
+                )),
+                index: Box::new(Expr::without_table(
+                    1,
+                    ExprKind::Identifier("i".to_string()),
+                )),
+            }),
+            op: Some(BinaryOp::Pow),
+            value: Expr::without_table(3, ExprKind::Literal(Literal::Integer(2))),
+        }));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.statement();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn const_definition() {
+        let tokens = Lexer::new("const SIZE: Int = 2 * 3;").collect_vec();
+
+        let expected_ast = Ok(Stmt::without_table(3, StmtKind::DefineConst {
+            identifier: "SIZE".to_string(),
+            value: Expr::without_table(2, ExprKind::BinaryOp {
+                op: BinaryOp::Mul,
+                lhs: Box::new(Expr::without_table(
+                    0,
+                    ExprKind::Literal(Literal::Integer(2)),
+                )),
+                rhs: Box::new(Expr::without_table(
+                    1,
+                    ExprKind::Literal(Literal::Integer(3)),
+                )),
+            }),
+            typ: TypeIdentifier {
+                name: "Int".to_string(),
+                args: Vec::new(),
+                is_list: false,
+                is_pointer: false,
+            },
+        }));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.define_const();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
     #[test]
     fn basic_variable_definition() {
         let tokens = Lexer::new("var foo: Int = 5 + 3;").collect_vec();
@@ -378,7 +822,9 @@ mod tests {
             }),
             typ: Some(TypeIdentifier {
                 name: "Int".to_string(),
+                args: Vec::new(),
                 is_list: false,
+                is_pointer: false,
             }),
         }));
 
@@ -405,23 +851,28 @@ mod tests {
         .collect_vec();
 
         let expected_ast = Ok(Stmt::without_table(
-            11,
+            12,
             StmtKind::DefineFunction(Function {
                 identifier: "foo".to_owned(),
+                params: Vec::new(),
                 inputs: vec![FunctionInput {
                     identifier: "bar".to_owned(),
                     typ: TypeIdentifier {
                         name: "Int".to_owned(),
+                        args: Vec::new(),
                         is_list: false,
+                        is_pointer: false,
                     },
                 }],
                 output: Some(TypeIdentifier {
                     name: "Int".to_owned(),
+                    args: Vec::new(),
                     is_list: false,
+                    is_pointer: false,
                 }),
                 kind: FunctionKind::Normal {
                     body: Box::new(Stmt::without_table(
-                        10,
+                        11,
                         StmtKind::Block(vec![
                             Stmt::without_table(3, StmtKind::DefineVariable {
                                 identifier: "baz".to_owned(),
@@ -438,29 +889,35 @@ mod tests {
                                 }),
                                 typ: Some(TypeIdentifier {
                                     name: "Int".to_owned(),
+                                    args: Vec::new(),
                                     is_list: false,
+                                    is_pointer: false,
                                 }),
                             }),
-                            Stmt::without_table(7, StmtKind::AssignVariable {
-                                identifier: "baz".to_owned(),
-                                value: Expr::without_table(6, ExprKind::BinaryOp {
+                            Stmt::without_table(8, StmtKind::Assign {
+                                target: Expr::without_table(
+                                    4,
+                                    ExprKind::Identifier("baz".to_owned()),
+                                ),
+                                op: None,
+                                value: Expr::without_table(7, ExprKind::BinaryOp {
                                     op: BinaryOp::Add,
                                     lhs: Box::new(Expr::without_table(
-                                        4,
+                                        5,
                                         ExprKind::Identifier("baz".to_owned()),
                                     )),
                                     rhs: Box::new(Expr::without_table(
-                                        5,
+                                        6,
                                         Literal::Integer(1).into(),
                                     )),
                                 }),
                             }),
                             Stmt::without_table(
-                                9,
-                                StmtKind::Return(Expr::without_table(
-                                    8,
+                                10,
+                                StmtKind::Return(Some(Expr::without_table(
+                                    9,
                                     ExprKind::Identifier("baz".to_owned()),
-                                )),
+                                ))),
                             ),
                         ]),
                     )),
@@ -477,6 +934,257 @@ mod tests {
         assert_eq!(expected_ast, generated_ast);
     }
 
+    #[test]
+    fn module_imports() {
+        let tokens = Lexer::new("use foo; use foo::bar; 0;").collect_vec();
+
+        let expected_ast = Ok(Stmt::without_table(
+            4,
+            StmtKind::Block(vec![
+                Stmt::without_table(0, StmtKind::Use(vec!["foo".to_owned()])),
+                Stmt::without_table(1, StmtKind::Use(vec!["foo".to_owned(), "bar".to_owned()])),
+                Stmt::without_table(
+                    3,
+                    StmtKind::ExprStmt(Expr::without_table(2, Literal::Integer(0).into())),
+                ),
+            ]),
+        ));
+
+        let generated_ast = AstParser::parse(tokens, SymbolTable::new());
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn nested_imports_are_rejected() {
+        let tokens = Lexer::new("{ use foo; }").collect_vec();
+        assert!(AstParser::parse(tokens, SymbolTable::new()).is_err());
+    }
+
+    #[test]
+    fn nested_consts_are_rejected() {
+        let tokens = Lexer::new("fn f() { const A: Int = 1; }").collect_vec();
+        assert!(AstParser::parse(tokens, SymbolTable::new()).is_err());
+    }
+
+    #[test]
+    fn invalid_declared_types_are_rejected() {
+        let tokens = Lexer::new("var x: [Int = 3;").collect_vec();
+        assert!(AstParser::parse(tokens, SymbolTable::new()).is_err());
+    }
+
+    #[test]
+    fn error_recovery() {
+        let tokens = Lexer::new("var a = 5 +; { b = ; } fn c( { 0; } 1 }").collect_vec();
+        let errors = AstParser::parse(tokens, SymbolTable::new()).unwrap_err();
+
+        println!("Errors:\n{errors:#?}\n\n");
+
+        let found = errors
+            .iter()
+            .map(|it| match it {
+                ParsingError::UnexpectedToken(_, tt, _) => tt.clone(),
+                _ => panic!("Unexpected error {it:?}"),
+            })
+            .collect_vec();
+
+        assert_eq!(found, vec![
+            TokenType::SemiColon,
+            TokenType::SemiColon,
+            TokenType::OpeningBrace,
+            TokenType::ClosingBrace,
+        ]);
+    }
+
+    #[test]
+    fn generic_function() {
+        let tokens =
+            Lexer::new("fn peek<T, U>(stacks: [Stack<Stack<T>>], other: U) T { }").collect_vec();
+
+        let named = |name: &str, args| TypeIdentifier {
+            name: name.to_owned(),
+            args,
+            is_list: false,
+            is_pointer: false,
+        };
+        let expected_ast = Ok(Stmt::without_table(
+            1,
+            StmtKind::DefineFunction(Function {
+                identifier: "peek".to_owned(),
+                params: vec!["T".to_owned(), "U".to_owned()],
+                inputs: vec![
+                    FunctionInput {
+                        identifier: "stacks".to_owned(),
+                        typ: TypeIdentifier {
+                            name: "Stack".to_owned(),
+                            args: vec![named("Stack", vec![named("T", Vec::new())])],
+                            is_list: true,
+                            is_pointer: false,
+                        },
+                    },
+                    FunctionInput {
+                        identifier: "other".to_owned(),
+                        typ: named("U", Vec::new()),
+                    },
+                ],
+                output: Some(named("T", Vec::new())),
+                kind: FunctionKind::Normal {
+                    body: Box::new(Stmt::without_table(0, StmtKind::Block(Vec::new()))),
+                },
+            }),
+        ));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.statement();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn struct_definition() {
+        let tokens = Lexer::new("struct Point { x: Int, tags: [String], }").collect_vec();
+
+        let expected_ast = Ok(Stmt::without_table(
+            0,
+            StmtKind::DefineStruct(Struct {
+                identifier: "Point".to_owned(),
+                params: Vec::new(),
+                fields: vec![
+                    StructField {
+                        identifier: "x".to_owned(),
+                        typ: TypeIdentifier {
+                            name: "Int".to_owned(),
+                            args: Vec::new(),
+                            is_list: false,
+                            is_pointer: false,
+                        },
+                    },
+                    StructField {
+                        identifier: "tags".to_owned(),
+                        typ: TypeIdentifier {
+                            name: "String".to_owned(),
+                            args: Vec::new(),
+                            is_list: true,
+                            is_pointer: false,
+                        },
+                    },
+                ],
+            }),
+        ));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.statement();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn enum_definition() {
+        let tokens =
+            Lexer::new("enum Shape { Square(Int), Rect(Int, Float), Empty }").collect_vec();
+
+        let int = TypeIdentifier {
+            name: "Int".to_owned(),
+            args: Vec::new(),
+            is_list: false,
+            is_pointer: false,
+        };
+        let float = TypeIdentifier {
+            name: "Float".to_owned(),
+            args: Vec::new(),
+            is_list: false,
+            is_pointer: false,
+        };
+        let expected_ast = Ok(Stmt::without_table(
+            0,
+            StmtKind::DefineEnum(Enum {
+                identifier: "Shape".to_owned(),
+                variants: vec![
+                    EnumVariant {
+                        identifier: "Square".to_owned(),
+                        fields: vec![int.clone()],
+                    },
+                    EnumVariant {
+                        identifier: "Rect".to_owned(),
+                        fields: vec![int, float],
+                    },
+                    EnumVariant {
+                        identifier: "Empty".to_owned(),
+                        fields: vec![],
+                    },
+                ],
+            }),
+        ));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.statement();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+    }
+
+    #[test]
+    fn field_assignment() {
+        let tokens = Lexer::new("line.start.x = 5;").collect_vec();
+
+        let expected_ast = Ok(Stmt::without_table(4, StmtKind::Assign {
+            target: Expr::without_table(2, ExprKind::FieldAccess {
+                value: Box::new(Expr::without_table(1, ExprKind::FieldAccess {
+                    value: Box::new(Expr::without_table(
+                        0,
+                        ExprKind::Identifier("line".to_owned()),
+                    )),
+                    field: "start".to_owned(),
+                })),
+                field: "x".to_owned(),
+            }),
+            op: None,
+            value: Expr::without_table(3, Literal::Integer(5).into()),
+        }));
+
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        let generated_ast = parser.statement();
+
+        println!("Expected AST:\n{expected_ast:#?}\n\n");
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        assert_eq!(expected_ast, generated_ast);
+
+        let tokens = Lexer::new("foo().x = 5;").collect_vec();
+        let mut parser = AstParser::new(tokens, SymbolTable::new());
+        assert!(matches!(
+            parser.statement(),
+            Err(ParsingError::InvalidAssignment(_))
+        ));
+    }
+
+    #[test]
+    fn no_struct_literal_in_condition() {
+        let tokens = Lexer::new("if foo { } while (Foo {}).bar { }").collect_vec();
+        let generated_ast = AstParser::parse(tokens, SymbolTable::new());
+
+        println!("Generated AST:\n{generated_ast:#?}\n\n");
+
+        let StmtKind::Block(stmts) = generated_ast.unwrap().kind else {
+            panic!("Expected a block");
+        };
+        let StmtKind::IfStmt { condition, .. } = &stmts[0].kind else {
+            panic!("Expected an if statement");
+        };
+        assert_eq!(condition.kind, ExprKind::Identifier("foo".to_owned()));
+    }
+
     #[test]
     fn basic_conditional() {
         let tokens = Lexer::new(
@@ -1,17 +1,29 @@
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::{Cell, OnceCell, Ref, RefCell, RefMut};
 use std::collections::hash_map::Entry::Vacant;
 use std::collections::HashMap;
+use std::fmt::{Debug, Display};
 use std::rc::Rc;
 
-use crate::parser::ast::TypeIdentifier;
+use itertools::Itertools;
+
+use crate::lexer::Span;
+use crate::parser::ast::{Literal, TypeIdentifier};
 
 #[derive(Debug, Default)]
 struct Scope {
     parent: Option<Rc<Scope>>,
     symbols: RefCell<HashMap<String, Symbol>>,
+
+    /// Scopes of other modules whose symbols are visible from this scope. Only
+    /// the symbols defined directly in an imported scope are visible, its
+    /// parents and imports are not.
+    imports: OnceCell<Vec<Rc<Scope>>>,
+
+    /// Counter used to hand out symbol ids, only used on the root scope so ids
+    /// are unique across every module of a program.
+    next_id: Cell<i32>,
 }
 
-#[derive(Debug)]
 pub struct SymbolTable(Rc<Scope>);
 
 impl SymbolTable {
@@ -33,6 +45,29 @@ impl SymbolTable {
         Some(Self(self.0.parent.clone()?))
     }
 
+    /// Make the symbols defined at the top level of the given tables visible
+    /// from this table. Imports can only be set once.
+    pub fn set_imports(&self, imports: &[SymbolTable]) {
+        let imports = imports.iter().map(|it| it.0.clone()).collect();
+        self.0
+            .imports
+            .set(imports)
+            .expect("Imports of a symbol table can only be set once");
+    }
+
+    /// Reserve an id for a new symbol. Ids are unique between all tables
+    /// sharing the same root.
+    pub fn reserve_id(&self) -> i32 {
+        let mut root = &self.0;
+        while let Some(parent) = &root.parent {
+            root = parent;
+        }
+
+        let id = root.next_id.get();
+        root.next_id.set(id + 1);
+        id
+    }
+
     pub fn contains(&self, identifier: &str) -> bool {
         for scope in self.iter() {
             if scope.symbols.borrow().contains_key(identifier) {
@@ -54,13 +89,24 @@ impl SymbolTable {
         None
     }
 
+    /// Type named by the identifier. Generic types are instantiated with the
+    /// type arguments of the identifier, None if their number doesn't match.
     pub fn get_type(&self, identifier: &TypeIdentifier) -> Option<Type> {
         let symbol = self.get(&identifier.name)?;
         if let Symbol::Type(ref typ) = *symbol {
-            let mut typ = typ.clone();
+            let args = identifier
+                .args
+                .iter()
+                .map(|it| self.get_type(it))
+                .collect::<Option<Vec<_>>>()?;
+
+            let mut typ = typ.instantiate(&args)?;
             if identifier.is_list {
                 typ = Type::Array { typ: Box::new(typ) };
             }
+            if identifier.is_pointer {
+                typ = Type::Pointer { typ: Box::new(typ) };
+            }
             return Some(typ);
         }
 
@@ -100,10 +146,21 @@ impl SymbolTable {
     fn iter(&self) -> Iter<'_> {
         Iter {
             next: Some(&self.0),
+            imports: Default::default(),
         }
     }
 }
 
+impl Debug for SymbolTable {
+    /// Only the names defined directly in the table are shown, as the same
+    /// parent scopes would otherwise be printed for every node of an AST.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries(self.0.symbols.borrow().keys().sorted())
+            .finish()
+    }
+}
+
 impl Default for SymbolTable {
     fn default() -> Self {
         Self::new()
@@ -116,16 +173,24 @@ impl Clone for SymbolTable {
     }
 }
 
+/// Iterator over every scope visible from a table. Scopes are visited
+/// innermost first, each followed by the scopes it imports.
 struct Iter<'a> {
     next: Option<&'a Scope>,
+    imports: std::slice::Iter<'a, Rc<Scope>>,
 }
 
 impl<'a> Iterator for Iter<'a> {
     type Item = &'a Scope;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(import) = self.imports.next() {
+            return Some(import);
+        }
+
         self.next.map(|scope| {
             self.next = scope.parent.as_deref();
+            self.imports = scope.imports.get().map(|it| it.iter()).unwrap_or_default();
             scope
         })
     }
@@ -144,6 +209,14 @@ pub struct ValueSymbol {
     pub typ: Type,
     pub id: i32,
     pub mutable: bool,
+    /// Where the value is declared, None for values built into the compiler
+    pub span: Option<Span>,
+    /// Value of the symbol if it is a constant, set once the value of the
+    /// constant is evaluated
+    pub constant: Option<Literal>,
+    /// Names of the inputs if the value is a function, used to point out
+    /// mismatched arguments
+    pub inputs: Vec<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
@@ -163,4 +236,226 @@ pub enum Type {
     Array {
         typ: Box<Type>,
     },
+    /// Pointer to a value of the type, created by referencing a place with
+    /// `*` and followed with `@`.
+    Pointer {
+        typ: Box<Type>,
+    },
+    /// A struct, fields are in the order they were declared in. Generic
+    /// structs are defined with their type parameters as arguments.
+    Struct {
+        name: String,
+        args: Vec<Type>,
+        fields: Vec<(String, Type)>,
+    },
+    /// An enum, variants are in the order they were declared in which also
+    /// gives them their tag.
+    Enum {
+        name: String,
+        variants: Vec<(String, Vec<Type>)>,
+    },
+    /// Type parameter of a generic function or struct, replaced by a concrete
+    /// type whenever the generic is used.
+    Param(String),
+    /// Type of a declaration whose value couldn't be typed. The declaration
+    /// is still defined so its uses don't report it a second time.
+    Unknown,
+}
+
+impl Type {
+    /// Whether the type mentions any type parameters.
+    pub fn is_generic(&self) -> bool {
+        self.any_param(&|_| true)
+    }
+
+    /// Whether the type mentions the type parameter.
+    pub fn mentions(&self, param: &str) -> bool {
+        self.any_param(&|it| it == param)
+    }
+
+    fn any_param(&self, predicate: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Type::Param(name) => predicate(name),
+            Type::Iterator { typ } | Type::Array { typ } | Type::Pointer { typ } => {
+                typ.any_param(predicate)
+            }
+            Type::Function { inputs, output } => {
+                inputs.iter().any(|it| it.any_param(predicate)) || output.any_param(predicate)
+            }
+            Type::Struct { args, .. } => args.iter().any(|it| it.any_param(predicate)),
+            Type::Enum { variants, .. } => variants
+                .iter()
+                .flat_map(|(_, fields)| fields)
+                .any(|it| it.any_param(predicate)),
+            _ => false,
+        }
+    }
+
+    /// Replace the type parameters with the types they are mapped to,
+    /// parameters without a mapping are kept.
+    pub fn substitute(&self, args: &HashMap<String, Type>) -> Type {
+        let substitute = |typ: &Type| typ.substitute(args);
+
+        match self {
+            Type::Param(name) => args.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Iterator { typ } => Type::Iterator {
+                typ: Box::new(substitute(typ)),
+            },
+            Type::Array { typ } => Type::Array {
+                typ: Box::new(substitute(typ)),
+            },
+            Type::Pointer { typ } => Type::Pointer {
+                typ: Box::new(substitute(typ)),
+            },
+            Type::Function { inputs, output } => Type::Function {
+                inputs: inputs.iter().map(substitute).collect(),
+                output: Box::new(substitute(output)),
+            },
+            Type::Struct { name, args, fields } => Type::Struct {
+                name: name.clone(),
+                args: args.iter().map(substitute).collect(),
+                fields: fields
+                    .iter()
+                    .map(|(field, typ)| (field.clone(), substitute(typ)))
+                    .collect(),
+            },
+            Type::Enum { name, variants } => Type::Enum {
+                name: name.clone(),
+                variants: variants
+                    .iter()
+                    .map(|(variant, fields)| {
+                        (variant.clone(), fields.iter().map(substitute).collect())
+                    })
+                    .collect(),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Match this type against a concrete one, mapping the type parameters it
+    /// mentions to the types at their position. Returns false if the types
+    /// don't match or a parameter would have to stand for two types.
+    pub fn unify(&self, concrete: &Type, args: &mut HashMap<String, Type>) -> bool {
+        match (self, concrete) {
+            (Type::Param(name), _) => match args.get(name) {
+                Some(typ) => typ == concrete,
+                None => {
+                    args.insert(name.clone(), concrete.clone());
+                    true
+                }
+            },
+            (Type::Iterator { typ }, Type::Iterator { typ: other })
+            | (Type::Array { typ }, Type::Array { typ: other })
+            | (Type::Pointer { typ }, Type::Pointer { typ: other }) => typ.unify(other, args),
+            (
+                Type::Function { inputs, output },
+                Type::Function {
+                    inputs: other_inputs,
+                    output: other_output,
+                },
+            ) => {
+                inputs.len() == other_inputs.len()
+                    && inputs
+                        .iter()
+                        .zip(other_inputs)
+                        .all(|(it, other)| it.unify(other, args))
+                    && output.unify(other_output, args)
+            }
+            (
+                Type::Struct {
+                    name, args: params, ..
+                },
+                Type::Struct {
+                    name: other_name,
+                    args: other_args,
+                    ..
+                },
+            ) => {
+                name == other_name
+                    && params.len() == other_args.len()
+                    && params
+                        .iter()
+                        .zip(other_args)
+                        .all(|(it, other)| it.unify(other, args))
+            }
+            _ => self == concrete,
+        }
+    }
+
+    /// Instantiate a generic struct with type arguments, None if the number
+    /// of arguments doesn't match its type parameters. Other types take no
+    /// arguments.
+    pub fn instantiate(&self, args: &[Type]) -> Option<Type> {
+        let params = match self {
+            Type::Struct { args: params, .. } => params.as_slice(),
+            _ => &[],
+        };
+
+        if params.len() != args.len() {
+            return None;
+        }
+
+        let args = params
+            .iter()
+            .zip(args)
+            .filter_map(|(param, arg)| match param {
+                Type::Param(name) => Some((name.clone(), arg.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Some(self.substitute(&args))
+    }
+
+    /// Index and type of a field, None if the type isn't a struct or has no
+    /// field with that name.
+    pub fn field(&self, name: &str) -> Option<(usize, &Type)> {
+        let Type::Struct { fields, .. } = self else {
+            return None;
+        };
+
+        fields
+            .iter()
+            .enumerate()
+            .find(|(_, (field, _))| field == name)
+            .map(|(index, (_, typ))| (index, typ))
+    }
+
+    /// Tag and field types of a variant, None if the type isn't an enum or has
+    /// no variant with that name.
+    pub fn variant(&self, name: &str) -> Option<(usize, &[Type])> {
+        let Type::Enum { variants, .. } = self else {
+            return None;
+        };
+
+        variants
+            .iter()
+            .enumerate()
+            .find(|(_, (variant, _))| variant == name)
+            .map(|(tag, (_, fields))| (tag, fields.as_slice()))
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Void => write!(f, "Void"),
+            Type::Integer => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::Boolean => write!(f, "Bool"),
+            Type::String => write!(f, "String"),
+            Type::Iterator { typ } => write!(f, "Iterator<{typ}>"),
+            Type::Function { inputs, output } => {
+                write!(f, "fn({}) {output}", inputs.iter().join(", "))
+            }
+            Type::Array { typ } => write!(f, "[{typ}]"),
+            Type::Pointer { typ } => write!(f, "*{typ}"),
+            Type::Struct { name, args, .. } if !args.is_empty() => {
+                write!(f, "{name}<{}>", args.iter().join(", "))
+            }
+            Type::Struct { name, .. } | Type::Enum { name, .. } => write!(f, "{name}"),
+            Type::Param(name) => write!(f, "{name}"),
+            Type::Unknown => write!(f, "{{unknown}}"),
+        }
+    }
 }
@@ -0,0 +1,76 @@
+#![cfg(feature = "llvm")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn write_source(test: &str, source: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("sloth-bounds-{test}"));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    let path = directory.join("main.sloth");
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn run(path: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sloth"))
+        .arg("run")
+        .arg(path)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn assert_panics(output: &Output, message: &str) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(101), "stderr: {stderr}");
+    assert!(stderr.contains(message), "stderr: {stderr}");
+}
+
+#[test]
+fn index_past_the_end() {
+    let path = write_source(
+        "past-the-end",
+        "fn main() Int { val values = [1, 2, 3]; val i = vlen(values); return values[i]; }",
+    );
+
+    for args in [&[][..], &["--jit"]] {
+        let output = run(&path, args);
+        assert_panics(&output, "the size is 3 but the index is 3");
+    }
+}
+
+#[test]
+fn negative_index() {
+    let path = write_source(
+        "negative",
+        "fn main() Int { var values = [1, 2, 3]; val i = 0 - 1; values[i] = 4; return 0; }",
+    );
+
+    let output = run(&path, &[]);
+    assert_panics(&output, "the size is 3 but the index is -1");
+}
+
+#[test]
+fn pop_from_empty_vector() {
+    let path = write_source(
+        "pop-empty",
+        "fn main() Int { var values = [1]; vpop(values); return vpop(values); }",
+    );
+
+    let output = run(&path, &[]);
+    assert_panics(&output, "the size is 0 but the index is -1");
+}
+
+#[test]
+fn index_in_bounds() {
+    let path = write_source(
+        "in-bounds",
+        "fn main() Int { val values = [1, 2, 3]; return values[vlen(values) - 1]; }",
+    );
+
+    let output = run(&path, &[]);
+    assert_eq!(output.status.code(), Some(3));
+}
@@ -25,6 +25,16 @@ int wait(int msec) {
     return res;
 }
 
+void sloth_index_panic(int index, int size) {
+    fprintf(stderr, "panic: index out of bounds, the size is %d but the index is %d\n", size, index);
+    exit(101);
+}
+
+void sloth_alloc_panic(size_t bytes) {
+    fprintf(stderr, "panic: out of memory, failed to allocate %zu bytes\n", bytes);
+    exit(101);
+}
+
 int slen(char *str) {
     return (int) strlen(str);
 }
@@ -53,6 +63,13 @@ bool sequals(char* a, char* b) {
     return true;
 }
 
+char* sconcat(char* a, char* b) {
+    char* res = malloc(strlen(a) + strlen(b) + 1);
+    strcpy(res, a);
+    strcat(res, b);
+    return res;
+}
+
 char* istr(int x) {
     char* snum = malloc(12);
     sprintf(snum, "%d", x);